@@ -1,25 +1,86 @@
 struct CameraUniform {
+    view_position: vec4<f32>,
     view_proj: mat4x4<f32>,
 }
 
+struct ModelUniform {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+}
+
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+@group(1) @binding(1)
+var<uniform> model: ModelUniform;
+
+struct DirectionalLight {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+}
+
+struct Lights {
+    view_proj: mat4x4<f32>,
+    directional: DirectionalLight,
+    points: array<PointLight, 8>,
+    point_count: u32,
+}
+
+@group(2) @binding(0)
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) tint: vec4<f32>,
+    @location(10) normal_0: vec3<f32>,
+    @location(11) normal_1: vec3<f32>,
+    @location(12) normal_2: vec3<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_normal: vec3<f32>,
 }
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let instance_model = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    let world_model = model.model * instance_model;
+    let world_position = world_model * vec4<f32>(in.position, 1.0);
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.tint = instance.tint;
+    out.world_position = world_position.xyz;
+    let instance_normal = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+    out.world_normal = model.normal * instance_normal * in.normal;
+    out.clip_position = camera.view_proj * world_position;
     return out;
 }
 
@@ -28,7 +89,70 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+struct Material {
+    ambient: f32,
+    specular: f32,
+    shininess: f32,
+}
+
+@group(0) @binding(2)
+var<uniform> material: Material;
+
+struct Light {
+    diffuse: vec3<f32>,
+    specular: vec3<f32>,
+}
+
+// Blinn-Phong response to light arriving from `to_light` (normalized).
+fn blinn_phong(normal: vec3<f32>, to_view: vec3<f32>, to_light: vec3<f32>, color: vec3<f32>) -> Light {
+    let half_dir = normalize(to_light + to_view);
+    var light: Light;
+    light.diffuse = color * max(dot(normal, to_light), 0.0);
+    light.specular = color * material.specular
+        * pow(max(dot(normal, half_dir), 0.0), material.shininess);
+    return light;
+}
+
+// Fraction of the directional light reaching `world_position`, averaged over a 3x3 texel
+// neighbourhood of the shadow map. Points outside the shadow map are lit.
+fn sun_visibility(world_position: vec3<f32>) -> f32 {
+    let light_space = lights.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_space.xyz / light_space.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+
+    var visibility = 0.0;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            visibility = visibility + textureSampleCompare(t_shadow, s_shadow, uv + offset, ndc.z);
+        }
+    }
+
+    let outside = any(uv < vec2<f32>(0.0, 0.0)) || any(uv > vec2<f32>(1.0, 1.0)) || ndc.z > 1.0;
+    return select(visibility / 9.0, 1.0, outside);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    let normal = normalize(in.world_normal);
+    let to_view = normalize(camera.view_position.xyz - in.world_position);
+
+    let sun_color = lights.directional.color * sun_visibility(in.world_position);
+    let sun = blinn_phong(normal, to_view, -lights.directional.direction, sun_color);
+    var diffuse = sun.diffuse;
+    var specular = sun.specular;
+    for (var i = 0u; i < min(lights.point_count, 8u); i = i + 1u) {
+        let point_light = lights.points[i];
+        let offset = point_light.position - in.world_position;
+        let distance = length(offset);
+        let falloff = clamp(1.0 - distance / point_light.range, 0.0, 1.0);
+        let light = blinn_phong(normal, to_view, offset / distance, point_light.color * falloff * falloff);
+        diffuse = diffuse + light.diffuse;
+        specular = specular + light.specular;
+    }
+
+    let color = albedo.rgb * (vec3<f32>(material.ambient) + diffuse) + specular;
+    return vec4<f32>(color, albedo.a);
 }
@@ -0,0 +1,36 @@
+struct Lights {
+    view_proj: mat4x4<f32>,
+}
+
+struct ModelUniform {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> lights: Lights;
+
+@group(1) @binding(1)
+var<uniform> model: ModelUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let instance_model = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    return lights.view_proj * model.model * instance_model * vec4<f32>(in.position, 1.0);
+}
@@ -0,0 +1,38 @@
+struct Screen {
+    size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_atlas: sampler;
+@group(0) @binding(2)
+var<uniform> screen: Screen;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = in.position / screen.size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.tex_coords).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
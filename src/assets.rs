@@ -0,0 +1,224 @@
+use crate::{
+    err::Error,
+    mesh::MeshAsset,
+    mipmap::MipmapGenerator,
+    texture::{TextureOptions, TextureState},
+};
+use image::ImageFormat;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs, io, mem,
+    path::{Path, PathBuf},
+};
+use wgpu::{
+    BindGroupLayout, Device, ErrorFilter, Queue, ShaderModule, ShaderModuleDescriptor, ShaderSource,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TextureHandle(usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ShaderHandle(usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MeshHandle(usize);
+
+/// Loads textures, meshes and WGSL shaders from a directory at runtime and keeps one GPU copy of
+/// each, keyed by path relative to that directory.
+pub struct AssetManager {
+    root: PathBuf,
+    textures: Vec<TextureState>,
+    texture_handles: HashMap<(PathBuf, TextureOptions), TextureHandle>,
+    shaders: Vec<ShaderModule>,
+    shader_handles: HashMap<PathBuf, ShaderHandle>,
+    meshes: Vec<MeshAsset>,
+    mesh_handles: HashMap<PathBuf, MeshHandle>,
+    mipmaps: MipmapGenerator,
+}
+
+impl AssetManager {
+    /// Directory used when neither the caller nor `TOWN_LINKS_ASSETS` picks one.
+    pub const DEFAULT_ROOT: &'static str = "resources";
+
+    #[inline]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            textures: Vec::new(),
+            texture_handles: HashMap::new(),
+            shaders: Vec::new(),
+            shader_handles: HashMap::new(),
+            meshes: Vec::new(),
+            mesh_handles: HashMap::new(),
+            mipmaps: MipmapGenerator::new(),
+        }
+    }
+
+    /// Directory named by the `TOWN_LINKS_ASSETS` environment variable, or `DEFAULT_ROOT`.
+    pub fn root_from_env() -> PathBuf {
+        std::env::var_os("TOWN_LINKS_ASSETS")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(Self::DEFAULT_ROOT))
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn load_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        let path = path.as_ref();
+        let key = (path.to_path_buf(), options);
+        if let Some(handle) = self.texture_handles.get(&key) {
+            return Ok(*handle);
+        }
+
+        let full_path = self.root.join(path);
+        let bytes = read(&full_path)?;
+        let image = ImageFormat::from_path(&full_path)
+            .and_then(|format| image::load_from_memory_with_format(&bytes, format))
+            .map_err(|err| Error::AssetDecodeError(full_path.clone(), err))?;
+        let label = path.to_string_lossy();
+        let texture = TextureState::from_image(
+            device,
+            queue,
+            &mut self.mipmaps,
+            &image,
+            Some(&label),
+            options,
+        )?;
+
+        let handle = TextureHandle(self.textures.len());
+        self.textures.push(texture);
+        self.texture_handles.insert(key, handle);
+        Ok(handle)
+    }
+
+    pub fn load_shader(
+        &mut self,
+        device: &Device,
+        path: impl AsRef<Path>,
+    ) -> Result<ShaderHandle, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.shader_handles.get(path) {
+            return Ok(*handle);
+        }
+
+        let shader = self.compile_shader(device, path)?;
+
+        let handle = ShaderHandle(self.shaders.len());
+        self.shaders.push(shader);
+        self.shader_handles.insert(path.to_path_buf(), handle);
+        Ok(handle)
+    }
+
+    /// Loads a glTF or OBJ file, binding its base-color textures with
+    /// `texture_bind_group_layout`.
+    pub fn load_mesh(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        texture_bind_group_layout: &BindGroupLayout,
+        path: impl AsRef<Path>,
+    ) -> Result<MeshHandle, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.mesh_handles.get(path) {
+            return Ok(*handle);
+        }
+
+        let full_path = self.root.join(path);
+        if !full_path.is_file() {
+            return Err(Error::AssetNotFound(full_path));
+        }
+        let mesh = MeshAsset::load(
+            device,
+            queue,
+            &mut self.mipmaps,
+            texture_bind_group_layout,
+            &full_path,
+        )?;
+
+        let handle = MeshHandle(self.meshes.len());
+        self.meshes.push(mesh);
+        self.mesh_handles.insert(path.to_path_buf(), handle);
+        Ok(handle)
+    }
+
+    /// Reads and compiles a WGSL file, bypassing the cache. Validation failures are returned
+    /// as `Error::ShaderCompilationError` instead of reaching the device's error handler.
+    pub fn compile_shader(
+        &self,
+        device: &Device,
+        path: impl AsRef<Path>,
+    ) -> Result<ShaderModule, Error> {
+        let path = path.as_ref();
+        let full_path = self.root.join(path);
+        let source = read_to_string(&full_path)?;
+
+        device.push_error_scope(ErrorFilter::Validation);
+        let label = path.to_string_lossy();
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(&label),
+            source: ShaderSource::Wgsl(Cow::Owned(source)),
+        });
+        match pollster::block_on(device.pop_error_scope()) {
+            None => Ok(shader),
+            Some(err) => Err(Error::ShaderCompilationError(full_path, err.to_string())),
+        }
+    }
+
+    #[inline]
+    pub fn texture(&self, handle: TextureHandle) -> &TextureState {
+        let TextureHandle(index) = handle;
+        &self.textures[index]
+    }
+
+    #[inline]
+    pub fn mesh(&self, handle: MeshHandle) -> &MeshAsset {
+        let MeshHandle(index) = handle;
+        &self.meshes[index]
+    }
+
+    #[inline]
+    pub fn shader(&self, handle: ShaderHandle) -> &ShaderModule {
+        let ShaderHandle(index) = handle;
+        &self.shaders[index]
+    }
+
+    /// The shader loaded from `path`, if it was.
+    #[inline]
+    pub fn shader_handle(&self, path: impl AsRef<Path>) -> Option<ShaderHandle> {
+        self.shader_handles.get(path.as_ref()).copied()
+    }
+
+    /// Swaps in `shader` for the one behind `handle`, e.g. after recompiling it, and returns
+    /// the old one.
+    #[inline]
+    pub fn replace_shader(&mut self, handle: ShaderHandle, shader: ShaderModule) -> ShaderModule {
+        let ShaderHandle(index) = handle;
+        mem::replace(&mut self.shaders[index], shader)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|err| asset_error(path, err))
+}
+
+fn read_to_string(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|err| asset_error(path, err))
+}
+
+fn asset_error(path: &Path, err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::NotFound {
+        Error::AssetNotFound(path.to_path_buf())
+    } else {
+        Error::AssetIoError(path.to_path_buf(), err)
+    }
+}
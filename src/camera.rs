@@ -1,19 +1,35 @@
 use crate::mat4x4_uniform::Mat4x4Uniform;
 use bytemuck::{Pod, Zeroable};
-use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+use cgmath::{
+    ortho, perspective, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3, VectorSpace,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Projection {
+    /// Uses the camera's `fovy`.
+    Perspective,
+    /// Parallel projection showing `height` world units from the bottom to the top of the
+    /// viewport, e.g. for a flat map view from above.
+    Orthographic { height: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Camera {
     pub eye: Point3<f32>,
     pub target: Point3<f32>,
     pub up: Vector3<f32>,
     pub aspect: f32,
+    pub projection: Projection,
+    /// Vertical field of view in degrees, for `Projection::Perspective`.
     pub fovy: f32,
     pub znear: f32,
     pub zfar: f32,
 }
 
 #[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+pub(crate) const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0, 
     0.0, 0.0, 0.5, 0.0, 
@@ -21,22 +37,204 @@ const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
 );
 
 impl Camera {
+    /// Distance from the eye to the target below which there is no view direction to switch
+    /// projections along.
+    pub const MIN_TOGGLE_DISTANCE: f32 = 1e-4;
+
     pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
         let view = Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        let proj = match self.projection {
+            Projection::Perspective => {
+                perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar)
+            }
+            Projection::Orthographic { height } => {
+                let (half_width, half_height) = (height * 0.5 * self.aspect, height * 0.5);
+                ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        };
 
         OPENGL_TO_WGPU_MATRIX * proj * view
     }
+
+    /// Height in world units of the view at `distance` in front of the eye.
+    pub fn view_height_at(&self, distance: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective => 2.0 * distance * self.half_fovy_tan(),
+            Projection::Orthographic { height } => height,
+        }
+    }
+
+    /// Origin and direction of the ray through a point of the viewport, given in normalized
+    /// device coordinates with y up.
+    pub fn ray(&self, x: f32, y: f32) -> (Point3<f32>, Vector3<f32>) {
+        let forward = (self.target - self.eye).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        match self.projection {
+            Projection::Perspective => {
+                let half_height = self.half_fovy_tan();
+                let direction =
+                    forward + right * x * half_height * self.aspect + up * y * half_height;
+                (self.eye, direction.normalize())
+            }
+            Projection::Orthographic { height } => {
+                let offset = right * x * height * 0.5 * self.aspect + up * y * height * 0.5;
+                (self.eye + offset, forward)
+            }
+        }
+    }
+
+    /// Blends from this camera to `next` by `alpha` between 0 and 1, e.g. to render between two
+    /// simulation steps. Switching projections and the viewport's shape take effect at once;
+    /// `ProjectionTransition` animates the former.
+    pub fn interpolate(&self, next: &Camera, alpha: f32) -> Camera {
+        let projection = match (self.projection, next.projection) {
+            (Projection::Perspective, Projection::Perspective) => Projection::Perspective,
+            (Projection::Orthographic { height }, Projection::Orthographic { height: next }) => {
+                Projection::Orthographic {
+                    height: height + (next - height) * alpha,
+                }
+            }
+            _ => return *next,
+        };
+        Camera {
+            eye: Point3::from_vec(self.eye.to_vec().lerp(next.eye.to_vec(), alpha)),
+            target: Point3::from_vec(self.target.to_vec().lerp(next.target.to_vec(), alpha)),
+            up: self.up.lerp(next.up, alpha),
+            projection,
+            fovy: self.fovy + (next.fovy - self.fovy) * alpha,
+            ..*next
+        }
+    }
+
+    /// Switches between perspective and orthographic projection. The view around `target`
+    /// keeps its size on screen: the orthographic view is as tall as the perspective one at
+    /// the target, and going back moves the eye to where the perspective view is as tall.
+    ///
+    /// Returns whether it switched, which it refuses to with the eye on the target.
+    pub fn toggle_projection(&mut self) -> bool {
+        let offset = self.target - self.eye;
+        if offset.magnitude() < Self::MIN_TOGGLE_DISTANCE {
+            return false;
+        }
+        match self.projection {
+            Projection::Perspective => {
+                self.projection = Projection::Orthographic {
+                    height: self.view_height_at(offset.magnitude()),
+                };
+            }
+            Projection::Orthographic { height } => {
+                self.projection = Projection::Perspective;
+                let distance = height / (2.0 * self.half_fovy_tan());
+                self.eye = self.target - offset.normalize() * distance;
+            }
+        }
+        true
+    }
+
+    #[inline]
+    fn half_fovy_tan(&self) -> f32 {
+        (Rad::from(Deg(self.fovy)).0 * 0.5).tan()
+    }
+}
+
+/// Animates a switch between projections as a dolly zoom: the field of view narrows while
+/// the eye backs away, keeping the view around the target the same size, until it is close
+/// enough to parallel to go orthographic. Going back to perspective runs it in reverse.
+///
+/// The camera itself switches at once, so input and picking always see the new projection;
+/// only the rendered camera is animated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProjectionTransition {
+    to_orthographic: bool,
+    previous_elapsed: Duration,
+    elapsed: Duration,
+}
+
+impl ProjectionTransition {
+    pub const DURATION: Duration = Duration::from_millis(400);
+    /// Field of view in degrees the dolly zoom narrows to before switching to orthographic.
+    const MIN_FOVY: f32 = 1.0;
+
+    /// A transition to `camera`'s projection, which it has just switched to. Switching back
+    /// during a transition reverses it from where it is.
+    pub fn new(camera: &Camera, current: Option<&ProjectionTransition>) -> Self {
+        let to_orthographic = camera.projection != Projection::Perspective;
+        let elapsed = match current {
+            Some(current) if current.to_orthographic != to_orthographic => {
+                Self::DURATION.saturating_sub(current.elapsed)
+            }
+            _ => Duration::ZERO,
+        };
+        Self {
+            to_orthographic,
+            previous_elapsed: elapsed,
+            elapsed,
+        }
+    }
+
+    /// Advances the transition by one simulation step of `timestep`. Returns whether it is
+    /// still running.
+    pub fn update(&mut self, timestep: Duration) -> bool {
+        self.previous_elapsed = self.elapsed;
+        self.elapsed = (self.elapsed + timestep).min(Self::DURATION);
+        self.previous_elapsed < Self::DURATION
+    }
+
+    /// Turns `camera`, which has the projection being switched to, into the camera to render
+    /// `alpha` of the way between the last two simulation steps.
+    pub fn apply(&self, camera: &mut Camera, alpha: f32) {
+        let progress = |elapsed: Duration| elapsed.as_secs_f32() / Self::DURATION.as_secs_f32();
+        let previous = progress(self.previous_elapsed);
+        let t = previous + (progress(self.elapsed) - previous) * alpha;
+        // Ease in and out.
+        let t = t * t * (3.0 - 2.0 * t);
+        let perspective = if self.to_orthographic { 1.0 - t } else { t };
+
+        let offset = camera.target - camera.eye;
+        let distance = offset.magnitude();
+        if distance < Camera::MIN_TOGGLE_DISTANCE {
+            // The eye was moved onto the target since, leaving no direction to dolly along.
+            return;
+        }
+        let height = camera.view_height_at(distance);
+        if perspective <= 0.0 {
+            camera.projection = Projection::Orthographic { height };
+            return;
+        }
+
+        let min_half_fovy_tan = (Rad::from(Deg(Self::MIN_FOVY)).0 * 0.5).tan();
+        let half_fovy_tan =
+            min_half_fovy_tan + (camera.half_fovy_tan() - min_half_fovy_tan) * perspective;
+        let dolly = height / (2.0 * half_fovy_tan) - distance;
+        camera.eye -= offset.normalize() * dolly;
+        // Clip the same stretch of the scene from further back.
+        camera.znear += dolly;
+        camera.zfar += dolly;
+        camera.fovy = Deg::from(Rad(2.0 * half_fovy_tan.atan())).0;
+        camera.projection = Projection::Perspective;
+    }
 }
 
 #[repr(C)]
 #[derive(Default, Copy, Clone, Debug, Pod, Zeroable)]
-pub struct CameraUniform(Mat4x4Uniform);
+pub struct CameraUniform {
+    view_position: [f32; 4],
+    view_proj: Mat4x4Uniform,
+}
 
 impl CameraUniform {
     #[inline]
     pub fn update_view_proj(&mut self, camera: &Camera) {
-        let Self(mat) = self;
-        mat.update_mat(camera.build_view_projection_matrix());
+        self.view_position = camera.eye.to_homogeneous().into();
+        self.view_proj
+            .update_mat(camera.build_view_projection_matrix());
     }
 }
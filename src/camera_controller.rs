@@ -1,9 +1,44 @@
-use crate::camera::Camera;
-use cgmath::InnerSpace;
-use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use crate::{camera::Camera, input_map::Action};
+use cgmath::{InnerSpace, Vector3};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+use winit::event::ElementState;
+
+/// Keeps mouse look from flipping over when looking straight up or down.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CameraMode {
+    /// W/S move towards and away from the target, A/D circle around it.
+    #[default]
+    Orbit,
+    /// Mouse motion turns the camera in place and W/A/S/D fly in the view direction. The
+    /// cursor is grabbed and hidden.
+    MouseLook,
+    /// The mouse orbits, pans and zooms with `MapCameraController`; this controller leaves the
+    /// camera alone.
+    Map,
+}
+
+impl CameraMode {
+    /// Switches to `mode`, or back to `Orbit` if `mode` is already active.
+    #[inline]
+    pub fn toggle(self, mode: Self) -> Self {
+        if self == mode {
+            Self::Orbit
+        } else {
+            mode
+        }
+    }
+}
 
 pub struct CameraController {
     speed: f32,
+    mode: CameraMode,
+    sensitivity: f32,
+    invert_y: bool,
+    /// Mouse motion since the last update, in pixels.
+    mouse_delta: (f64, f64),
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
@@ -11,10 +46,17 @@ pub struct CameraController {
 }
 
 impl CameraController {
+    /// Radians turned per pixel of mouse motion by default.
+    pub const DEFAULT_SENSITIVITY: f32 = 0.003;
+
     #[inline]
     pub fn new(speed: f32) -> Self {
         Self {
             speed,
+            mode: CameraMode::default(),
+            sensitivity: Self::DEFAULT_SENSITIVITY,
+            invert_y: false,
+            mouse_delta: (0.0, 0.0),
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
@@ -22,43 +64,104 @@ impl CameraController {
         }
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state,
-                        virtual_keycode: Some(keycode),
-                        ..
-                    },
-                ..
-            } => {
-                let is_pressed = *state == ElementState::Pressed;
-                match keycode {
-                    VirtualKeyCode::W | VirtualKeyCode::Up => {
-                        self.is_forward_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::S | VirtualKeyCode::Down => {
-                        self.is_backward_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::A | VirtualKeyCode::Left => {
-                        self.is_left_pressed = is_pressed;
-                        true
-                    }
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
-                        self.is_right_pressed = is_pressed;
-                        true
-                    }
-                    _ => false,
-                }
-            }
-            _ => false,
+    #[inline]
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    /// Radians the camera turns per pixel of mouse motion.
+    #[inline]
+    pub fn sensitivity_mut(&mut self) -> &mut f32 {
+        &mut self.sensitivity
+    }
+
+    /// Whether moving the mouse up looks down in mouse-look mode.
+    #[inline]
+    pub fn invert_y_mut(&mut self) -> &mut bool {
+        &mut self.invert_y
+    }
+
+    /// Distance the camera moves per update while a key is held.
+    #[inline]
+    pub fn speed_mut(&mut self) -> &mut f32 {
+        &mut self.speed
+    }
+
+    /// Starts or stops moving for the movement actions; returns whether `action` is one.
+    pub fn process_action(&mut self, action: Action, state: ElementState) -> bool {
+        let is_pressed = state == ElementState::Pressed;
+        match action {
+            Action::MoveForward => self.is_forward_pressed = is_pressed,
+            Action::MoveBackward => self.is_backward_pressed = is_pressed,
+            Action::MoveLeft => self.is_left_pressed = is_pressed,
+            Action::MoveRight => self.is_right_pressed = is_pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Adds raw mouse motion, e.g. from `DeviceEvent::MouseMotion`. Ignored outside
+    /// mouse-look mode.
+    #[inline]
+    pub fn process_mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        if self.mode == CameraMode::MouseLook {
+            self.mouse_delta.0 += dx;
+            self.mouse_delta.1 += dy;
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera) {
+        match self.mode {
+            CameraMode::Orbit => self.update_orbit(camera),
+            CameraMode::MouseLook => self.update_mouse_look(camera),
+            CameraMode::Map => {}
+        }
+    }
+
+    fn update_mouse_look(&mut self, camera: &mut Camera) {
+        let offset = camera.target - camera.eye;
+        let distance = offset.magnitude();
+        let forward = offset / distance;
+
+        let (dx, dy) = self.mouse_delta;
+        self.mouse_delta = (0.0, 0.0);
+        let dy = if self.invert_y { -dy } else { dy };
+        let yaw = forward.z.atan2(forward.x) + dx as f32 * self.sensitivity;
+        let pitch = (forward.y.asin() - dy as f32 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let forward = Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        let right = forward.cross(camera.up).normalize();
+
+        let mut movement = Vector3::new(0.0, 0.0, 0.0);
+        if self.is_forward_pressed {
+            movement += forward;
+        }
+        if self.is_backward_pressed {
+            movement -= forward;
+        }
+        if self.is_right_pressed {
+            movement += right;
+        }
+        if self.is_left_pressed {
+            movement -= right;
+        }
+        if movement.magnitude2() > 0.0 {
+            camera.eye += movement.normalize() * self.speed;
         }
+        camera.target = camera.eye + forward * distance;
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
+    fn update_orbit(&self, camera: &mut Camera) {
         let forward = camera.target - camera.eye;
         let forward_norm = forward.normalize();
         let forward_mag = forward.magnitude();
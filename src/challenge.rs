@@ -1,4 +1,6 @@
-#[derive(Copy, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum ChallengeEnum {
     First,
     Second,
@@ -6,7 +8,7 @@ pub enum ChallengeEnum {
     Fourth,
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Challenge(Option<ChallengeEnum>);
 
 impl Challenge {
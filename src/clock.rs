@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+/// Longest frame the clock catches up on. Longer stalls, e.g. while the window is dragged,
+/// are dropped instead of being simulated in one burst of steps.
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Shortest timestep, which bounds the number of steps per frame.
+const MIN_TIMESTEP: Duration = Duration::from_millis(1);
+
+/// Fastest time scale, which together with `MAX_FRAME_TIME` bounds the simulated time per
+/// frame.
+pub const MAX_SCALE: f32 = 16.0;
+
+/// What a frame should simulate and how far it is between two simulation steps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameTime {
+    /// Simulated time that passed this frame: real time, scaled and zero while paused.
+    pub delta: Duration,
+    /// Number of fixed timesteps to simulate this frame.
+    pub steps: u32,
+    /// How far the simulation is between the last step and the next one, from 0 to 1. The
+    /// renderer blends the last two states by this much.
+    pub alpha: f32,
+}
+
+/// Measures frame times and splits them into fixed simulation timesteps, so the simulation
+/// runs at the same speed whatever the frame rate.
+pub struct Clock {
+    timestep: Duration,
+    scale: f32,
+    paused: bool,
+    last_tick: Option<Instant>,
+    /// Simulated time not yet covered by a step.
+    accumulator: Duration,
+    elapsed: Duration,
+}
+
+impl Default for Clock {
+    /// A clock stepping 60 times per simulated second.
+    #[inline]
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1) / 60)
+    }
+}
+
+impl Clock {
+    /// A clock simulating `timestep` per step, at least a millisecond.
+    #[inline]
+    pub fn new(timestep: Duration) -> Self {
+        Self {
+            timestep: timestep.max(MIN_TIMESTEP),
+            scale: 1.0,
+            paused: false,
+            last_tick: None,
+            accumulator: Duration::ZERO,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    /// Simulated time covered by all steps so far.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Simulated seconds per real second; 1 runs in real time.
+    #[inline]
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Sets the simulated seconds per real second, clamped between 0 and `MAX_SCALE`. NaN and
+    /// infinite scales are ignored.
+    #[inline]
+    pub fn set_scale(&mut self, scale: f32) {
+        if scale.is_finite() {
+            self.scale = scale.clamp(0.0, MAX_SCALE);
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    #[inline]
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    #[inline]
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Advances by the real time since the last tick. The first tick advances by nothing.
+    pub fn tick(&mut self) -> FrameTime {
+        let real = self.lap();
+        self.advance(real)
+    }
+
+    /// Real time since the last tick or lap, without advancing. Zero the first time.
+    pub fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        match self.last_tick.replace(now) {
+            Some(last_tick) => now - last_tick,
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Advances by `real` time, e.g. to step the simulation deterministically in tests.
+    pub fn advance(&mut self, real: Duration) -> FrameTime {
+        let delta = if self.paused {
+            Duration::ZERO
+        } else {
+            real.min(MAX_FRAME_TIME).mul_f32(self.scale)
+        };
+        self.accumulator += delta;
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            self.accumulator -= self.timestep;
+            self.elapsed += self.timestep;
+            steps += 1;
+        }
+
+        FrameTime {
+            delta,
+            steps,
+            alpha: self.accumulator.as_secs_f32() / self.timestep.as_secs_f32(),
+        }
+    }
+}
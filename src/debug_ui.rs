@@ -0,0 +1,286 @@
+use crate::{
+    camera::{Camera, Projection},
+    camera_controller::{CameraController, CameraMode},
+    challenge::Challenge,
+    clock::Clock,
+    err::Error,
+    input_map::{Action, InputMap},
+};
+use cgmath::Point3;
+use imgui::{ColorEdit, Condition, Context, FontSource, Io, Slider, Ui, Window as ImguiWindow};
+use imgui_wgpu::{Renderer, RendererConfig};
+use imgui_winit_support::{HiDpiMode, WinitPlatform};
+use std::time::Instant;
+use wgpu::{
+    Color, CommandEncoder, Device, LoadOp, Operations, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, SurfaceConfiguration, TextureFormat, TextureView,
+};
+use winit::{
+    event::{Event, WindowEvent},
+    window::Window,
+};
+
+const CHALLENGE_LABELS: [&str; 5] = [
+    "Default",
+    "Challenge 1",
+    "Challenge 2",
+    "Challenge 3",
+    "Challenge 4",
+];
+
+/// State the debug window shows and edits.
+pub struct DebugUiTargets<'a> {
+    pub camera: &'a mut Camera,
+    pub camera_controller: &'a mut CameraController,
+    pub challenge: &'a mut Challenge,
+    pub clear_color: &'a mut Color,
+    pub clock: &'a mut Clock,
+    /// Read for the key hints next to the settings that have an action.
+    pub input_map: &'a InputMap,
+}
+
+/// Dear ImGui window for inspecting and tweaking the renderer, drawn over the scene.
+///
+/// Dear ImGui only allows a single context per process, so at most one `DebugUi` can exist at
+/// a time.
+pub struct DebugUi {
+    context: Context,
+    platform: WinitPlatform,
+    renderer: Renderer,
+    window_attached: bool,
+    pub visible: bool,
+    last_frame: Instant,
+}
+
+impl DebugUi {
+    pub fn new(device: &Device, queue: &Queue, format: TextureFormat) -> Self {
+        let mut context = Context::create();
+        context.set_ini_filename(None);
+        context
+            .fonts()
+            .add_font(&[FontSource::DefaultFontData { config: None }]);
+        let platform = WinitPlatform::init(&mut context);
+
+        // The UI is drawn after the scene has been resolved, so without multisampling or depth.
+        let renderer_config = if format.describe().srgb {
+            RendererConfig::new()
+        } else {
+            RendererConfig::new_srgb()
+        };
+        let renderer = Renderer::new(
+            &mut context,
+            device,
+            queue,
+            RendererConfig {
+                texture_format: format,
+                ..renderer_config
+            },
+        );
+
+        Self {
+            context,
+            platform,
+            renderer,
+            window_attached: false,
+            visible: true,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Passes `event` on to the UI if it belongs to `window`, and updates the mouse buttons and
+    /// cursor the UI sees once the events of a frame have been handled.
+    pub fn handle_event<T>(&mut self, window: &Window, event: &Event<T>) -> Result<(), Error> {
+        let io = self.context.io_mut();
+        if !self.window_attached {
+            // Sizes are in physical pixels, like the rest of the renderer.
+            self.platform
+                .attach_window(io, window, HiDpiMode::Locked(1.0));
+            self.window_attached = true;
+        }
+        self.platform.handle_event(io, window, event);
+        if let Event::MainEventsCleared = event {
+            self.platform.prepare_frame(io, window)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the UI wants `event` for itself, e.g. clicks on the debug window or typing into
+    /// one of its fields.
+    pub fn wants_event(&self, event: &WindowEvent) -> bool {
+        if !self.visible {
+            return false;
+        }
+        let io = self.context.io();
+        match event {
+            WindowEvent::CursorMoved { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. } => io.want_capture_mouse,
+            WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => {
+                io.want_capture_keyboard
+            }
+            _ => false,
+        }
+    }
+
+    /// Dear ImGui's input state, for driving the UI without a window.
+    #[inline]
+    pub fn io_mut(&mut self) -> &mut Io {
+        self.context.io_mut()
+    }
+
+    /// Builds this frame's debug window for `targets` and draws it over each of `views`.
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        views: &[&TextureView],
+        config: &SurfaceConfiguration,
+        targets: DebugUiTargets,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let now = Instant::now();
+        let io = self.context.io_mut();
+        io.update_delta_time(now - self.last_frame);
+        io.display_size = [config.width as f32, config.height as f32];
+        self.last_frame = now;
+
+        let ui = self.context.frame();
+        build_window(&ui, targets);
+        let draw_data = ui.render();
+
+        let render_data = self.renderer.prepare(draw_data, None, queue, device);
+        for view in views {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Debug UI pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            if let Err(err) = self
+                .renderer
+                .split_render(draw_data, &render_data, &mut render_pass)
+            {
+                log::error!("can't draw the debug UI: {}", err);
+            }
+        }
+    }
+}
+
+fn build_window(ui: &Ui, targets: DebugUiTargets) {
+    let DebugUiTargets {
+        camera,
+        camera_controller,
+        challenge,
+        clear_color,
+        clock,
+        input_map,
+    } = targets;
+    // Dear ImGui identifies widgets by everything after `###`, so labels can change freely.
+    let label = |text: &str, action: Action| match input_map.describe(action) {
+        Some(keys) => format!("{} ({})###{}", text, keys, text),
+        None => format!("{}###{}", text, text),
+    };
+    let hint = |text: &str, action: Action| match input_map.describe(action) {
+        Some(keys) => format!("{} ({})", text, keys),
+        None => text.to_owned(),
+    };
+
+    ImguiWindow::new("Debug")
+        .position([8.0, 8.0], Condition::FirstUseEver)
+        .size([280.0, 220.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let mut eye: [f32; 3] = camera.eye.into();
+            if ui.input_float3("Eye", &mut eye).build() {
+                camera.eye = Point3::from(eye);
+            }
+            let mut target: [f32; 3] = camera.target.into();
+            if ui.input_float3("Target", &mut target).build() {
+                camera.target = Point3::from(target);
+            }
+            let mut orthographic = camera.projection != Projection::Perspective;
+            if ui.checkbox(
+                label("Orthographic", Action::ToggleProjection),
+                &mut orthographic,
+            ) {
+                camera.toggle_projection();
+            }
+            match &mut camera.projection {
+                Projection::Perspective => {
+                    Slider::new("Fov y", 1.0, 179.0).build(ui, &mut camera.fovy);
+                }
+                Projection::Orthographic { height } => {
+                    Slider::new("View height", 0.1, 100.0).build(ui, height);
+                }
+            }
+            ui.input_float("Near", &mut camera.znear).build();
+            ui.input_float("Far", &mut camera.zfar).build();
+            camera.znear = camera.znear.max(0.001);
+            camera.zfar = camera.zfar.max(camera.znear + 0.001);
+            Slider::new("Speed", 0.0, 1.0).build(ui, camera_controller.speed_mut());
+
+            ui.separator();
+
+            let mode = match camera_controller.mode() {
+                CameraMode::Orbit => "orbit".to_owned(),
+                CameraMode::MouseLook => hint("mouse look", Action::ToggleMouseLook),
+                CameraMode::Map => hint("map", Action::ToggleMapCamera),
+            };
+            ui.text(format!("Camera mode: {}", mode));
+            Slider::new("Sensitivity", 0.0005, 0.01).build(ui, camera_controller.sensitivity_mut());
+            ui.checkbox("Invert Y", camera_controller.invert_y_mut());
+
+            ui.separator();
+
+            let challenges = challenges();
+            let mut index = challenges
+                .iter()
+                .position(|other| other == challenge)
+                .unwrap_or_default();
+            if ui.combo_simple_string("Challenge", &mut index, &CHALLENGE_LABELS) {
+                *challenge = challenges[index];
+            }
+
+            let mut color = [
+                clear_color.r as f32,
+                clear_color.g as f32,
+                clear_color.b as f32,
+            ];
+            if ColorEdit::new("Clear color", &mut color).build(ui) {
+                clear_color.r = color[0] as f64;
+                clear_color.g = color[1] as f64;
+                clear_color.b = color[2] as f64;
+            }
+
+            ui.separator();
+
+            ui.text(format!("Time: {:.2} s", clock.elapsed().as_secs_f32()));
+            let mut paused = clock.is_paused();
+            if ui.checkbox(label("Paused", Action::TogglePause), &mut paused) {
+                clock.set_paused(paused);
+            }
+            let mut scale = clock.scale();
+            if Slider::new("Time scale", 0.0, 4.0).build(ui, &mut scale) {
+                clock.set_scale(scale);
+            }
+        });
+}
+
+/// Every challenge, in the order `Action::NextChallenge` cycles through them.
+fn challenges() -> [Challenge; CHALLENGE_LABELS.len()] {
+    let mut challenge = Challenge::default();
+    [(); CHALLENGE_LABELS.len()].map(|_| {
+        let current = challenge;
+        challenge = challenge.rotate();
+        current
+    })
+}
@@ -1,17 +1,229 @@
+use crate::texture::TextureOptions;
+use ab_glyph::InvalidFont;
 use image::ImageError;
 use log::SetLoggerError;
-use wgpu::{RequestDeviceError, SurfaceError};
+use std::{error, fmt, io, path::PathBuf};
+use wgpu::{RequestDeviceError, SurfaceError, TextureFormat};
 use winit::error::{ExternalError, OsError};
 
 #[derive(Debug)]
 pub enum Error {
-    ImageImageError(ImageError),
+    /// An image asset that couldn't be decoded.
+    AssetDecodeError(PathBuf, ImageError),
+    /// An asset that exists but couldn't be read.
+    AssetIoError(PathBuf, io::Error),
+    /// An asset path with no file behind it.
+    AssetNotFound(PathBuf),
+    /// A bindings file that couldn't be read.
+    ConfigIoError(PathBuf, io::Error),
+    /// A bindings file that isn't valid RON for `InputMap`.
+    ConfigParseError(PathBuf, ron::error::SpannedError),
+    /// The directory of a frame capture, or a frame in it, that couldn't be written.
+    FrameCaptureIoError(PathBuf, io::Error),
+    /// The thread saving captured frames died before saving all of them.
+    FrameCaptureThreadPanicked,
+    /// A glTF file that couldn't be imported.
+    GltfError(PathBuf, gltf::Error),
+    /// A glTF image with fewer or more pixels than its dimensions call for.
+    ImageBufferSizeError(PathBuf),
+    /// Encoded image bytes for the texture with this label that couldn't be decoded.
+    ImageImageError(String, ImageError),
+    /// A rendered image, like a screenshot or a captured frame, that couldn't be saved.
+    ImageSaveError(PathBuf, ImageError),
+    /// A texture's anisotropy clamp that wgpu doesn't accept, with the texture's label.
+    InvalidAnisotropyClamp(Option<String>, u8),
+    /// The bundled font failed to parse.
+    InvalidFont(InvalidFont),
+    /// A logger was already installed.
     LogSetLoggerError(SetLoggerError),
+    /// An OBJ file, or its material library, that couldn't be loaded.
+    ObjError(PathBuf, tobj::LoadError),
+    /// A recording that couldn't be created, written or read.
+    RecordingIoError(PathBuf, io::Error),
+    /// A recording with a malformed line, numbered from 1.
+    RecordingParseError(PathBuf, usize, ron::error::SpannedError),
+    /// A header or frame that couldn't be serialized into a recording.
+    RecordingWriteError(PathBuf, ron::Error),
+    /// No adapter could draw to the surface, or offscreen for headless states.
     RequestAdapterError,
+    /// The screenshot directory, or a screenshot in it, that couldn't be created.
+    ScreenshotIoError(PathBuf, io::Error),
+    /// A WGSL file that failed validation, with wgpu's message.
+    ShaderCompilationError(PathBuf, String),
+    /// A glTF image in a pixel format that can't be turned into a texture.
+    UnsupportedImageFormat(PathBuf, gltf::image::Format),
+    /// A mesh file that is neither glTF nor OBJ.
+    UnsupportedMeshFormat(PathBuf),
+    /// The formats the surface offered, none of which the renderer can draw to.
+    UnsupportedSurfaceFormat(Vec<TextureFormat>),
+    /// A texture format `TextureState` can't upload images to, with the texture's label.
+    UnsupportedTextureFormat(Option<String>, TextureFormat),
+    /// A buffer read back from the GPU that failed to map.
+    WgpuBufferAsyncError,
+    /// The adapter refused to create a device.
     WgpuRequestDeviceError(RequestDeviceError),
+    /// The next frame couldn't be taken from the surface.
     WgpuSurfaceError(SurfaceError),
-    WinitOsError(OsError),
+    /// The cursor couldn't be moved or grabbed.
     WinitExternalError(ExternalError),
+    /// The window couldn't be created.
+    WinitOsError(OsError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AssetDecodeError(path, _) => {
+                write!(f, "failed to decode image {}", path.display())
+            }
+            Self::AssetIoError(path, _) => write!(f, "failed to read asset {}", path.display()),
+            Self::AssetNotFound(path) => write!(f, "asset not found: {}", path.display()),
+            Self::ConfigIoError(path, _) => write!(f, "failed to read config {}", path.display()),
+            Self::ConfigParseError(path, _) => {
+                write!(f, "failed to parse config {}", path.display())
+            }
+            Self::FrameCaptureIoError(path, _) => {
+                write!(f, "failed to capture frames to {}", path.display())
+            }
+            Self::FrameCaptureThreadPanicked => {
+                write!(f, "frame capture thread panicked while saving frames")
+            }
+            Self::GltfError(path, _) => write!(f, "failed to load glTF {}", path.display()),
+            Self::ImageBufferSizeError(path) => write!(
+                f,
+                "image data in {} doesn't match the image dimensions",
+                path.display()
+            ),
+            Self::ImageImageError(label, _) => {
+                write!(f, "failed to decode the image of texture {}", label)
+            }
+            Self::ImageSaveError(path, _) => write!(f, "failed to save image {}", path.display()),
+            Self::InvalidAnisotropyClamp(Some(label), clamp) => write!(
+                f,
+                "invalid anisotropy clamp {} for texture {}, expected a power of two up to {}",
+                clamp,
+                label,
+                TextureOptions::MAX_ANISOTROPY
+            ),
+            Self::InvalidAnisotropyClamp(None, clamp) => write!(
+                f,
+                "invalid anisotropy clamp {}, expected a power of two up to {}",
+                clamp,
+                TextureOptions::MAX_ANISOTROPY
+            ),
+            Self::InvalidFont(_) => write!(f, "failed to load the bundled Roboto font"),
+            Self::LogSetLoggerError(_) => write!(f, "failed to set up logging"),
+            Self::ObjError(path, _) => write!(f, "failed to load OBJ {}", path.display()),
+            Self::RecordingIoError(path, _) => {
+                write!(f, "failed to access recording {}", path.display())
+            }
+            Self::RecordingParseError(path, line, _) => write!(
+                f,
+                "failed to parse recording {} on line {}",
+                path.display(),
+                line
+            ),
+            Self::RecordingWriteError(path, _) => {
+                write!(f, "failed to write recording {}", path.display())
+            }
+            Self::RequestAdapterError => write!(f, "no suitable graphics adapter found"),
+            Self::ScreenshotIoError(path, _) => {
+                write!(f, "failed to save screenshot to {}", path.display())
+            }
+            Self::ShaderCompilationError(path, message) => write!(
+                f,
+                "failed to compile shader {}: {}",
+                path.display(),
+                message
+            ),
+            Self::UnsupportedImageFormat(path, format) => write!(
+                f,
+                "unsupported pixel format {:?} in {}",
+                format,
+                path.display()
+            ),
+            Self::UnsupportedMeshFormat(path) => {
+                write!(f, "unsupported mesh format: {}", path.display())
+            }
+            Self::UnsupportedSurfaceFormat(formats) => {
+                write!(f, "no supported surface format among {:?}", formats)
+            }
+            Self::UnsupportedTextureFormat(Some(label), format) => {
+                write!(f, "unsupported format {:?} for texture {}", format, label)
+            }
+            Self::UnsupportedTextureFormat(None, format) => {
+                write!(f, "unsupported texture format {:?}", format)
+            }
+            Self::WgpuBufferAsyncError => write!(f, "failed to map a GPU buffer for reading"),
+            Self::WgpuRequestDeviceError(_) => write!(f, "failed to request a graphics device"),
+            Self::WgpuSurfaceError(_) => {
+                write!(f, "failed to acquire the next frame from the surface")
+            }
+            Self::WinitExternalError(_) => write!(f, "failed to move or grab the cursor"),
+            Self::WinitOsError(_) => write!(f, "failed to create the window"),
+        }
+    }
+}
+
+impl Error {
+    /// Displays the error followed by each of its sources, e.g.
+    /// `failed to read config bindings.ron: No such file or directory (os error 2)`. `Display`
+    /// only shows the error itself.
+    #[inline]
+    pub fn report(&self) -> Report<'_> {
+        Report(self)
+    }
+}
+
+/// An error and its chain of sources, from `Error::report`.
+pub struct Report<'a>(&'a Error);
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = error::Error::source(self.0);
+        while let Some(err) = source {
+            write!(f, ": {}", err)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::AssetDecodeError(_, err)
+            | Self::ImageImageError(_, err)
+            | Self::ImageSaveError(_, err) => Some(err),
+            Self::AssetIoError(_, err)
+            | Self::ConfigIoError(_, err)
+            | Self::FrameCaptureIoError(_, err)
+            | Self::RecordingIoError(_, err)
+            | Self::ScreenshotIoError(_, err) => Some(err),
+            Self::ConfigParseError(_, err) | Self::RecordingParseError(_, _, err) => Some(err),
+            Self::GltfError(_, err) => Some(err),
+            Self::InvalidFont(err) => Some(err),
+            Self::LogSetLoggerError(err) => Some(err),
+            Self::ObjError(_, err) => Some(err),
+            Self::RecordingWriteError(_, err) => Some(err),
+            Self::WgpuRequestDeviceError(err) => Some(err),
+            Self::WgpuSurfaceError(err) => Some(err),
+            Self::WinitExternalError(err) => Some(err),
+            Self::WinitOsError(err) => Some(err),
+            Self::AssetNotFound(_)
+            | Self::FrameCaptureThreadPanicked
+            | Self::ImageBufferSizeError(_)
+            | Self::InvalidAnisotropyClamp(..)
+            | Self::RequestAdapterError
+            | Self::ShaderCompilationError(..)
+            | Self::UnsupportedImageFormat(..)
+            | Self::UnsupportedMeshFormat(_)
+            | Self::UnsupportedSurfaceFormat(_)
+            | Self::UnsupportedTextureFormat(..)
+            | Self::WgpuBufferAsyncError => None,
+        }
+    }
 }
 
 impl From<OsError> for Error {
@@ -49,9 +261,9 @@ impl From<ExternalError> for Error {
     }
 }
 
-impl From<ImageError> for Error {
+impl From<InvalidFont> for Error {
     #[inline]
-    fn from(err: ImageError) -> Self {
-        Self::ImageImageError(err)
+    fn from(err: InvalidFont) -> Self {
+        Self::InvalidFont(err)
     }
 }
@@ -1,7 +1,8 @@
-use crate::{err::Error, state::State};
+use crate::{assets::AssetManager, err::Error, input_map::InputMap, state::State};
+use std::{env, path::PathBuf};
 use wgpu::SurfaceError;
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{DeviceEvent, Event},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
@@ -11,49 +12,58 @@ pub async fn run() -> Result<(), Error> {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop)?;
 
-    let mut state = State::new(&window).await?;
+    let mut state = State::new(&window, AssetManager::root_from_env()).await?;
+    if env::var_os("TOWN_LINKS_HOT_RELOAD").is_some() {
+        state.enable_shader_hot_reload();
+    }
+    state.enable_debug_ui();
 
-    event_loop.run(move |event, _, control_flow| match event {
-        Event::WindowEvent {
-            ref event,
-            window_id,
-        } if window.id() == window_id => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => state.resize(*physical_size),
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size)
-                    }
-                    WindowEvent::CursorMoved { position, .. } => state.update_color(position),
-                    _ => {}
-                }
-            }
+    // Bindings come from TOWN_LINKS_BINDINGS, or else bindings.ron among the assets if present.
+    let bindings = env::var_os("TOWN_LINKS_BINDINGS")
+        .map(PathBuf::from)
+        .or_else(|| Some(state.assets().root().join("bindings.ron")).filter(|path| path.exists()));
+    if let Some(path) = bindings {
+        state.set_input_map(InputMap::load(path)?);
+    }
+    if let Some(path) = env::var_os("TOWN_LINKS_RECORD") {
+        state.start_recording(path)?;
+    }
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Err(err) = state.handle_debug_ui_event(&window, &event) {
+            eprintln!("{}", err.report());
         }
-        Event::RedrawRequested(window_id) if window.id() == window_id => {
-            state.update();
-            match state.render() {
-                Ok(_) => {}
-                Err(err) => match err {
-                    Error::WgpuSurfaceError(SurfaceError::Lost) => state.resize(state.get_size()),
-                    Error::WgpuSurfaceError(SurfaceError::OutOfMemory) => {
-                        eprintln!("{:?}", err);
-                        *control_flow = ControlFlow::Exit;
-                    }
-                    err => eprintln!("{:?}", err),
-                },
+        match event {
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window.id() == window_id => state.handle_window_event(event),
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => state.mouse_motion(delta),
+            Event::RedrawRequested(window_id) if window.id() == window_id => {
+                if let Err(err) = state.update_cursor(&window) {
+                    eprintln!("{}", err.report());
+                }
+                state.tick();
+                match state.render() {
+                    Ok(_) => {}
+                    Err(err) => match err {
+                        Error::WgpuSurfaceError(SurfaceError::Lost) => {
+                            state.resize(state.get_size())
+                        }
+                        Error::WgpuSurfaceError(SurfaceError::OutOfMemory) => {
+                            eprintln!("{}", err.report());
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        err => eprintln!("{}", err.report()),
+                    },
+                }
             }
+            Event::MainEventsCleared if state.quit_requested() => *control_flow = ControlFlow::Exit,
+            Event::MainEventsCleared => window.request_redraw(),
+            _ => {}
         }
-        Event::MainEventsCleared => window.request_redraw(),
-        _ => {}
     });
 }
@@ -0,0 +1,135 @@
+use crate::err::Error;
+use image::{ImageFormat, RgbaImage};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Frames waiting to be saved before `FrameCapture::capture` blocks, to bound memory use when
+/// encoding can't keep up.
+const QUEUE_LENGTH: usize = 8;
+
+/// Saves rendered frames as numbered PNGs, `frame-000000.png` and on, on a background thread.
+/// Tools like ffmpeg can turn the sequence into a video.
+pub struct FrameCapture {
+    dir: PathBuf,
+    every: u32,
+    frame_time: Duration,
+    /// Frames rendered since the capture started.
+    rendered: u64,
+    saved: u64,
+    sender: Option<SyncSender<(PathBuf, RgbaImage)>>,
+    worker: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl FrameCapture {
+    /// Video frame rate, in frames per simulated second, unless set otherwise.
+    pub const DEFAULT_FRAMES_PER_SECOND: u32 = 60;
+
+    /// Starts saving every `every`th rendered frame, at least every one, to `dir`.
+    pub fn start(dir: impl Into<PathBuf>, every: u32) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|err| Error::FrameCaptureIoError(dir.clone(), err))?;
+
+        let (sender, receiver) = mpsc::sync_channel::<(PathBuf, RgbaImage)>(QUEUE_LENGTH);
+        let worker = thread::spawn(move || {
+            for (path, image) in receiver {
+                image
+                    .save_with_format(&path, ImageFormat::Png)
+                    .map_err(|err| Error::ImageSaveError(path, err))?;
+            }
+            Ok(())
+        });
+
+        Ok(Self {
+            dir,
+            every: every.max(1),
+            frame_time: Duration::from_secs(1) / Self::DEFAULT_FRAMES_PER_SECOND,
+            rendered: 0,
+            saved: 0,
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Simulated time between two saved frames, so the sequence plays at the simulated speed
+    /// at `DEFAULT_FRAMES_PER_SECOND` unless set otherwise.
+    #[inline]
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Simulated time between two rendered frames, whatever the real time between them: a
+    /// `frame_time` spread over the `every` frames rendered per saved one.
+    #[inline]
+    pub fn render_time(&self) -> Duration {
+        self.frame_time / self.every
+    }
+
+    #[inline]
+    pub fn with_frame_time(mut self, frame_time: Duration) -> Self {
+        self.frame_time = frame_time;
+        self
+    }
+
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Frames handed to the background thread so far.
+    #[inline]
+    pub fn saved(&self) -> u64 {
+        self.saved
+    }
+
+    /// Counts a rendered frame and returns whether it is one to save.
+    #[inline]
+    pub fn next_frame(&mut self) -> bool {
+        let wanted = self.rendered.is_multiple_of(self.every as u64);
+        self.rendered += 1;
+        wanted
+    }
+
+    /// Queues `image` to be saved as the next frame of the sequence.
+    pub fn capture(&mut self, image: RgbaImage) -> Result<(), Error> {
+        let path = self.dir.join(format!("frame-{:06}.png", self.saved));
+        let sent = match &self.sender {
+            Some(sender) => sender.send((path, image)).is_ok(),
+            None => false,
+        };
+        if !sent {
+            // The background thread only hangs up after failing to save a frame.
+            return self.join();
+        }
+        self.saved += 1;
+        Ok(())
+    }
+
+    /// Waits for the queued frames to be saved and returns how many were.
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.join()?;
+        Ok(self.saved)
+    }
+
+    fn join(&mut self) -> Result<(), Error> {
+        self.sender = None;
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .unwrap_or(Err(Error::FrameCaptureThreadPanicked)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        if let Err(err) = self.join() {
+            log::error!("failed to save captured frames: {}", err.report());
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::err::Error;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::HashMap, fmt, fs, ops::BitOr, path::Path};
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+/// Something the user can do, independent of the input it is bound to.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Held while dragging to orbit the map camera.
+    MapOrbit,
+    /// Held while dragging to pan the map camera.
+    MapPan,
+    NextChallenge,
+    ToggleMouseLook,
+    ToggleMapCamera,
+    ToggleProjection,
+    TogglePause,
+    ToggleDebugUi,
+    Screenshot,
+    ToggleFrameCapture,
+    Quit,
+}
+
+impl Action {
+    /// Whether the action lasts as long as its trigger is held, rather than happening once per
+    /// press.
+    pub const fn is_continuous(self) -> bool {
+        matches!(
+            self,
+            Self::MoveForward
+                | Self::MoveBackward
+                | Self::MoveLeft
+                | Self::MoveRight
+                | Self::MapOrbit
+                | Self::MapPan
+        )
+    }
+}
+
+/// A key or mouse button.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Modifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Logo,
+}
+
+/// Input that triggers an action, as written in a bindings file, e.g. `Key(W)`,
+/// `Mouse(Left)` or `Chord([Ctrl], Key(R))`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    /// The trigger while all of the modifiers are held.
+    Chord(Vec<Modifier>, Trigger),
+}
+
+impl Binding {
+    fn split(&self) -> (ModifiersState, Trigger) {
+        match self {
+            Self::Key(keycode) => (ModifiersState::empty(), Trigger::Key(*keycode)),
+            Self::Mouse(button) => (ModifiersState::empty(), Trigger::Mouse(*button)),
+            Self::Chord(modifiers, trigger) => {
+                let modifiers = modifiers
+                    .iter()
+                    .map(|&modifier| ModifiersState::from(modifier))
+                    .fold(ModifiersState::empty(), BitOr::bitor);
+                (modifiers, *trigger)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    /// The key or button as shown to users, e.g. `F2` or `Left mouse`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(keycode) => write!(f, "{:?}", keycode),
+            Self::Mouse(button) => write!(f, "{:?} mouse", button),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    /// The binding as shown to users, e.g. `P` or `Ctrl+Shift+R`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(keycode) => Trigger::Key(*keycode).fmt(f),
+            Self::Mouse(button) => Trigger::Mouse(*button).fmt(f),
+            Self::Chord(modifiers, trigger) => {
+                for modifier in modifiers {
+                    write!(f, "{:?}+", modifier)?;
+                }
+                trigger.fmt(f)
+            }
+        }
+    }
+}
+
+impl From<Modifier> for ModifiersState {
+    #[inline]
+    fn from(modifier: Modifier) -> Self {
+        match modifier {
+            Modifier::Shift => Self::SHIFT,
+            Modifier::Ctrl => Self::CTRL,
+            Modifier::Alt => Self::ALT,
+            Modifier::Logo => Self::LOGO,
+        }
+    }
+}
+
+/// Maps key and mouse button events to actions. A press triggers the action whose chord has
+/// the most modifiers that are all held, so bindings without modifiers work with any held.
+/// Releasing the trigger releases the action it pressed. Presses repeated by the OS while the
+/// trigger is held only reach continuous actions.
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+    triggers: HashMap<Trigger, Vec<(ModifiersState, Action)>>,
+    modifiers: ModifiersState,
+    held: HashMap<Trigger, Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use VirtualKeyCode as Key;
+
+        Self::new(HashMap::from([
+            (
+                Action::MoveForward,
+                vec![Binding::Key(Key::W), Binding::Key(Key::Up)],
+            ),
+            (
+                Action::MoveBackward,
+                vec![Binding::Key(Key::S), Binding::Key(Key::Down)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Binding::Key(Key::A), Binding::Key(Key::Left)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Binding::Key(Key::D), Binding::Key(Key::Right)],
+            ),
+            (Action::MapOrbit, vec![Binding::Mouse(MouseButton::Left)]),
+            (
+                Action::MapPan,
+                vec![
+                    Binding::Mouse(MouseButton::Right),
+                    Binding::Mouse(MouseButton::Middle),
+                ],
+            ),
+            (Action::NextChallenge, vec![Binding::Key(Key::Space)]),
+            (Action::ToggleMouseLook, vec![Binding::Key(Key::M)]),
+            (Action::ToggleMapCamera, vec![Binding::Key(Key::F2)]),
+            (Action::ToggleProjection, vec![Binding::Key(Key::P)]),
+            (Action::TogglePause, vec![Binding::Key(Key::Pause)]),
+            (Action::ToggleDebugUi, vec![Binding::Key(Key::F1)]),
+            (Action::Screenshot, vec![Binding::Key(Key::F12)]),
+            (Action::ToggleFrameCapture, vec![Binding::Key(Key::F10)]),
+            (Action::Quit, vec![Binding::Key(Key::Escape)]),
+        ]))
+    }
+}
+
+impl InputMap {
+    pub fn new(bindings: HashMap<Action, Vec<Binding>>) -> Self {
+        let mut triggers: HashMap<Trigger, Vec<(ModifiersState, Action)>> = HashMap::new();
+        for (action, bindings) in &bindings {
+            for binding in bindings {
+                let (modifiers, trigger) = binding.split();
+                triggers
+                    .entry(trigger)
+                    .or_default()
+                    .push((modifiers, *action));
+            }
+        }
+        for actions in triggers.values_mut() {
+            // Prefer chords with more modifiers. Ties go to the action that comes first in
+            // `Action`, whatever order the bindings were written in.
+            actions.sort_by_key(|(modifiers, action)| {
+                (Reverse(modifiers.bits().count_ones()), *action)
+            });
+        }
+        Self {
+            bindings,
+            triggers,
+            modifiers: ModifiersState::empty(),
+            held: HashMap::new(),
+        }
+    }
+
+    /// Parses bindings in RON, e.g. `{ Quit: [Key(Escape), Chord([Ctrl], Key(Q))] }`. Actions
+    /// left out keep their default bindings; an empty list unbinds one.
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        let mut bindings = Self::default().bindings;
+        bindings.extend(ron::from_str::<HashMap<Action, Vec<Binding>>>(source)?);
+        Ok(Self::new(bindings))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|err| Error::ConfigIoError(path.to_owned(), err))?;
+        Self::from_ron(&source).map_err(|err| Error::ConfigParseError(path.to_owned(), err))
+    }
+
+    /// The bindings of every action.
+    #[inline]
+    pub fn bindings(&self) -> &HashMap<Action, Vec<Binding>> {
+        &self.bindings
+    }
+
+    /// The bindings of `action` as shown to users, e.g. `W/Up`, or `None` if it is unbound.
+    pub fn describe(&self, action: Action) -> Option<String> {
+        let bindings = self
+            .bindings
+            .get(&action)
+            .filter(|bindings| !bindings.is_empty())?;
+        Some(
+            bindings
+                .iter()
+                .map(Binding::to_string)
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    }
+
+    /// The action `event` presses or releases, if any. Also keeps track of the held modifiers.
+    pub fn map_event(&mut self, event: &WindowEvent) -> Option<(Action, ElementState)> {
+        let (trigger, state) = match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                return None;
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => (Trigger::Key(*keycode), *state),
+            WindowEvent::MouseInput { state, button, .. } => (Trigger::Mouse(*button), *state),
+            _ => return None,
+        };
+
+        match state {
+            ElementState::Pressed => {
+                if let Some(&action) = self.held.get(&trigger) {
+                    return action.is_continuous().then_some((action, state));
+                }
+                let (_, action) = self
+                    .triggers
+                    .get(&trigger)?
+                    .iter()
+                    .find(|(modifiers, _)| self.modifiers.contains(*modifiers))?;
+                self.held.insert(trigger, *action);
+                Some((*action, state))
+            }
+            ElementState::Released => Some((self.held.remove(&trigger)?, state)),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::model::{normal_matrix, Model};
+use bytemuck::{cast_slice, Pod, Zeroable};
+use std::{mem, ops::Range};
+use wgpu::{
+    vertex_attr_array, Buffer, BufferAddress, BufferDescriptor, BufferSlice, BufferUsages, Device,
+    Queue, VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+
+/// One copy of a mesh: where it is placed and the color its texture is multiplied by.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshInstance {
+    pub model: Model,
+    pub tint: [f32; 4],
+}
+
+impl MeshInstance {
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    #[inline]
+    pub fn new(model: Model, tint: [f32; 4]) -> Self {
+        Self { model, tint }
+    }
+
+    #[inline]
+    pub fn to_raw(&self) -> MeshInstanceRaw {
+        let model = self.model.build_model_matrix();
+        MeshInstanceRaw {
+            model: model.into(),
+            tint: self.tint,
+            normal: normal_matrix(model).into(),
+        }
+    }
+}
+
+impl Default for MeshInstance {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Model::default(), Self::WHITE)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct MeshInstanceRaw {
+    model: [[f32; 4]; 4],
+    tint: [f32; 4],
+    normal: [[f32; 3]; 3],
+}
+
+impl MeshInstanceRaw {
+    // Locations 0..5 are left for the per-vertex attributes.
+    const ATTRIBS: [VertexAttribute; 8] = vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+        9 => Float32x4,
+        10 => Float32x3,
+        11 => Float32x3,
+        12 => Float32x3,
+    ];
+
+    #[inline]
+    pub const fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// CPU list of instances mirrored into a vertex buffer that is reallocated when it runs out
+/// of room.
+pub struct InstanceBuffer {
+    instances: Vec<MeshInstance>,
+    buffer: Buffer,
+    capacity: usize,
+    dirty: bool,
+}
+
+impl InstanceBuffer {
+    pub fn new(device: &Device, instances: Vec<MeshInstance>) -> Self {
+        let capacity = instances.len().max(1).next_power_of_two();
+        Self {
+            instances,
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            dirty: true,
+        }
+    }
+
+    #[inline]
+    pub fn instances(&self) -> &[MeshInstance] {
+        &self.instances
+    }
+
+    /// Marks the instances as changed; they are re-uploaded by the next `upload`.
+    #[inline]
+    pub fn instances_mut(&mut self) -> &mut Vec<MeshInstance> {
+        self.dirty = true;
+        &mut self.instances
+    }
+
+    #[inline]
+    pub fn push(&mut self, instance: MeshInstance) {
+        self.instances_mut().push(instance);
+    }
+
+    #[inline]
+    pub fn range(&self) -> Range<u32> {
+        0..self.instances.len() as u32
+    }
+
+    #[inline]
+    pub fn slice(&self) -> BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+
+    pub fn upload(&mut self, device: &Device, queue: &Queue) {
+        if !self.dirty {
+            return;
+        }
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        let raw = self
+            .instances
+            .iter()
+            .map(MeshInstance::to_raw)
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.buffer, 0, cast_slice(&raw));
+        self.dirty = false;
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Instance buffer"),
+            size: (capacity * mem::size_of::<MeshInstanceRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
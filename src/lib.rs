@@ -1,11 +1,27 @@
-mod camera;
-mod camera_controller;
-mod challenge;
+pub mod assets;
+pub mod camera;
+pub mod camera_controller;
+pub mod challenge;
+pub mod clock;
+mod debug_ui;
 pub mod err;
 pub mod event_loop;
+pub mod frame_capture;
+pub mod input_map;
+pub mod instance;
+pub mod light;
+pub mod map_camera_controller;
 mod mat4x4_uniform;
-mod model;
+pub mod mesh;
+mod mipmap;
+pub mod model;
+pub mod msaa;
 mod pipeline;
-mod state;
-mod texture;
+pub mod recording;
+pub mod render_target;
+pub mod scene;
+mod shader_watcher;
+pub mod state;
+pub mod text;
+pub mod texture;
 mod vertex;
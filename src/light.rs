@@ -0,0 +1,328 @@
+use crate::{camera::OPENGL_TO_WGPU_MATRIX, mat4x4_uniform::Mat4x4Uniform, texture::TextureState};
+use bytemuck::{cast_slice, Pod, Zeroable};
+use cgmath::{ortho, InnerSpace, Matrix4, Point3, Vector3};
+use std::hash::{Hash, Hasher};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    Device, Queue, SamplerBindingType, ShaderStages, TextureSampleType, TextureViewDimension,
+};
+
+/// Size of the point light array in `shader.wgsl`; extra lights are ignored.
+pub const MAX_POINT_LIGHTS: usize = 8;
+
+/// Light coming from infinitely far away, e.g. the sun.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// Direction the light travels in.
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    /// Center of the region covered by the shadow map.
+    pub shadow_target: Point3<f32>,
+    /// Distance from `shadow_target` to the edges of the shadow map, and from the shadow map's
+    /// near and far planes.
+    pub shadow_extent: f32,
+}
+
+impl DirectionalLight {
+    /// Orthographic projection of the shadow region as seen from the light.
+    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
+        let direction = self.direction.normalize();
+        let eye = self.shadow_target - direction * self.shadow_extent;
+        let up = if direction.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+        let view = Matrix4::look_at_rh(eye, self.shadow_target, up);
+        let extent = self.shadow_extent;
+        let proj = ortho(-extent, extent, -extent, extent, 0.0, 2.0 * extent);
+
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+impl Default for DirectionalLight {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            direction: Vector3::new(-0.3, -0.5, -1.0),
+            color: [1.0, 1.0, 1.0],
+            shadow_target: Point3::new(0.0, 0.0, 0.0),
+            shadow_extent: 3.0,
+        }
+    }
+}
+
+/// Light shining in all directions from `position`, fading out to nothing at `range`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub color: [f32; 3],
+    pub range: f32,
+}
+
+impl PointLight {
+    #[inline]
+    pub fn new(position: Vector3<f32>, color: [f32; 3], range: f32) -> Self {
+        Self {
+            position,
+            color,
+            range,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lights {
+    pub directional: DirectionalLight,
+    pub points: Vec<PointLight>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct DirectionalLightRaw {
+    direction: [f32; 3],
+    _padding: u32,
+    color: [f32; 3],
+    _padding2: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct PointLightRaw {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct LightsUniform {
+    view_proj: Mat4x4Uniform,
+    directional: DirectionalLightRaw,
+    points: [PointLightRaw; MAX_POINT_LIGHTS],
+    point_count: u32,
+    _padding: [u32; 3],
+}
+
+impl LightsUniform {
+    pub fn update_lights(&mut self, lights: &Lights) {
+        self.view_proj
+            .update_mat(lights.directional.build_view_projection_matrix());
+        self.directional = DirectionalLightRaw {
+            direction: lights.directional.direction.normalize().into(),
+            color: lights.directional.color,
+            ..Default::default()
+        };
+        let count = lights.points.len().min(MAX_POINT_LIGHTS);
+        for (raw, light) in self.points.iter_mut().zip(&lights.points[..count]) {
+            *raw = PointLightRaw {
+                position: light.position.into(),
+                range: light.range,
+                color: light.color,
+                ..Default::default()
+            };
+        }
+        self.point_count = count as u32;
+    }
+}
+
+/// `Lights` together with the uniform buffer and the directional light's shadow map.
+///
+/// `bind_group` gives the lit shaders the lights and the shadow map; `shadow_bind_group` gives
+/// the shadow pass only the lights, since it renders into the shadow map.
+pub struct LightsState {
+    pub lights: Lights,
+    uniform: LightsUniform,
+    buffer: Buffer,
+    pub shadow_map: TextureState,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+    pub shadow_bind_group_layout: BindGroupLayout,
+    pub shadow_bind_group: BindGroup,
+}
+
+impl LightsState {
+    pub const SHADOW_MAP_SIZE: u32 = 1024;
+
+    pub fn new(device: &Device, lights: Lights) -> Self {
+        let mut uniform = LightsUniform::default();
+        uniform.update_lights(&lights);
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Lights buffer"),
+            contents: cast_slice(&[uniform]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let shadow_map =
+            TextureState::create_shadow_map(device, Self::SHADOW_MAP_SIZE, "Shadow map");
+
+        let uniform_entry = |visibility| BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry(ShaderStages::FRAGMENT),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+            label: Some("Lights bind group layout"),
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&shadow_map.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&shadow_map.sampler),
+                },
+            ],
+            label: Some("Lights bind group"),
+        });
+
+        let shadow_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                entries: &[uniform_entry(ShaderStages::VERTEX)],
+                label: Some("Shadow bind group layout"),
+            });
+
+        let shadow_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &shadow_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("Shadow bind group"),
+        });
+
+        Self {
+            lights,
+            uniform,
+            buffer,
+            shadow_map,
+            bind_group_layout,
+            bind_group,
+            shadow_bind_group_layout,
+            shadow_bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn update_uniform(&mut self, queue: &Queue) {
+        self.uniform.update_lights(&self.lights);
+        queue.write_buffer(&self.buffer, 0, cast_slice(&[self.uniform]));
+    }
+}
+
+/// Per-material Blinn-Phong response: `ambient` is the unlit fraction of the texture color,
+/// `specular` scales highlights of sharpness `shininess`.
+#[derive(Copy, Clone, Debug)]
+pub struct Phong {
+    pub ambient: f32,
+    pub specular: f32,
+    pub shininess: f32,
+}
+
+impl Phong {
+    /// Mostly diffuse with a faint, broad highlight.
+    pub const MATTE: Self = Self {
+        ambient: 0.1,
+        specular: 0.1,
+        shininess: 16.0,
+    };
+
+    #[inline]
+    pub fn new(ambient: f32, specular: f32, shininess: f32) -> Self {
+        Self {
+            ambient,
+            specular,
+            shininess,
+        }
+    }
+
+    #[inline]
+    fn bits(&self) -> [u32; 3] {
+        [
+            self.ambient.to_bits(),
+            self.specular.to_bits(),
+            self.shininess.to_bits(),
+        ]
+    }
+
+    #[inline]
+    pub fn to_uniform(self) -> PhongUniform {
+        PhongUniform {
+            ambient: self.ambient,
+            specular: self.specular,
+            shininess: self.shininess,
+            _padding: 0,
+        }
+    }
+}
+
+impl Default for Phong {
+    #[inline]
+    fn default() -> Self {
+        Self::MATTE
+    }
+}
+
+// Compared bitwise so materials can key bind group caches.
+impl PartialEq for Phong {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for Phong {}
+
+impl Hash for Phong {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct PhongUniform {
+    ambient: f32,
+    specular: f32,
+    shininess: f32,
+    _padding: u32,
+}
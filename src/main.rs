@@ -1,6 +1,12 @@
-use town_links::{err::Error, event_loop};
+use std::process::ExitCode;
+use town_links::event_loop;
 
-fn main() -> Result<(), Error> {
-    pollster::block_on(event_loop::run())?;
-    Ok(())
+fn main() -> ExitCode {
+    match pollster::block_on(event_loop::run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err.report());
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,200 @@
+use crate::{
+    camera::{Camera, Projection},
+    input_map::Action,
+};
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseScrollDelta, WindowEvent},
+};
+
+/// Pixels of touchpad scrolling that count as one mouse wheel line.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Bounds the map camera keeps its distance from the target and its pitch within.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MapCameraLimits {
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Lowest angle of the view above the ground plane.
+    pub min_pitch: Rad<f32>,
+    /// Highest angle of the view above the ground plane; at most just under straight down.
+    pub max_pitch: Rad<f32>,
+}
+
+impl Default for MapCameraLimits {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min_distance: 0.5,
+            max_distance: 50.0,
+            min_pitch: Deg(10.0).into(),
+            max_pitch: Deg(89.0).into(),
+        }
+    }
+}
+
+/// Mouse-driven camera for looking down on a map: dragging with `Action::MapOrbit` held, the
+/// left button by default, orbits around the target, dragging with `Action::MapPan` held, the
+/// right or middle button, pans the target across the ground plane through it, and the wheel
+/// zooms towards the point under the cursor.
+pub struct MapCameraController {
+    limits: MapCameraLimits,
+    /// Radians turned per pixel dragged.
+    orbit_speed: f32,
+    /// Fraction of the distance kept per wheel line zoomed in.
+    zoom_step: f32,
+    cursor: Option<PhysicalPosition<f64>>,
+    is_orbit_pressed: bool,
+    is_pan_pressed: bool,
+    /// Drags and scrolling since the last update.
+    orbit_delta: (f64, f64),
+    pan_delta: (f64, f64),
+    zoom_lines: f64,
+    zoom_cursor: Option<PhysicalPosition<f64>>,
+}
+
+impl MapCameraController {
+    #[inline]
+    pub fn new(limits: MapCameraLimits) -> Self {
+        Self {
+            limits,
+            orbit_speed: 0.01,
+            zoom_step: 0.9,
+            cursor: None,
+            is_orbit_pressed: false,
+            is_pan_pressed: false,
+            orbit_delta: (0.0, 0.0),
+            pan_delta: (0.0, 0.0),
+            zoom_lines: 0.0,
+            zoom_cursor: None,
+        }
+    }
+
+    #[inline]
+    pub fn limits_mut(&mut self) -> &mut MapCameraLimits {
+        &mut self.limits
+    }
+
+    /// Starts or stops orbiting and panning; returns whether `action` is one of them.
+    pub fn process_action(&mut self, action: Action, state: ElementState) -> bool {
+        let is_pressed = state == ElementState::Pressed;
+        match action {
+            Action::MapOrbit => self.is_orbit_pressed = is_pressed,
+            Action::MapPan => self.is_pan_pressed = is_pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Tracks the cursor for dragging and zooms with the wheel.
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let previous = self.cursor.replace(*position);
+                let delta = match previous {
+                    Some(previous) => (position.x - previous.x, position.y - previous.y),
+                    None => (0.0, 0.0),
+                };
+                if self.is_orbit_pressed {
+                    self.orbit_delta.0 += delta.0;
+                    self.orbit_delta.1 += delta.1;
+                }
+                if self.is_pan_pressed {
+                    self.pan_delta.0 += delta.0;
+                    self.pan_delta.1 += delta.1;
+                }
+                self.is_orbit_pressed || self.is_pan_pressed
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.zoom_lines += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(delta) => delta.y / PIXELS_PER_LINE,
+                };
+                self.zoom_cursor = self.cursor;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Follows the cursor while the map camera isn't in use, so the first zoom after switching
+    /// to it anchors on the point actually under the cursor.
+    pub fn track_cursor(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.cursor = Some(*position),
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            _ => {}
+        }
+    }
+
+    /// Applies the input since the last update to `camera`, which shows a `size` window.
+    pub fn update_camera(&mut self, camera: &mut Camera, size: PhysicalSize<u32>) {
+        let zoom_anchor = self
+            .zoom_cursor
+            .and_then(|cursor| ground_point_under(camera, size, cursor));
+        let offset = camera.eye - camera.target;
+        // An eye on the target has no direction to keep; it moves out to the closest distance.
+        let distance = offset.magnitude().max(self.limits.min_distance);
+        let mut yaw = offset.z.atan2(offset.x);
+        let mut pitch = (offset.y / distance).clamp(-1.0, 1.0).asin();
+
+        let (dx, dy) = self.orbit_delta;
+        yaw += dx as f32 * self.orbit_speed;
+        pitch += dy as f32 * self.orbit_speed;
+        let pitch = pitch.clamp(self.limits.min_pitch.0, self.limits.max_pitch.0);
+
+        // One pixel at the target's depth covers this much of the world.
+        let pixel_size = camera.view_height_at(distance) / size.height.max(1) as f32;
+        let (dx, dy) = self.pan_delta;
+        let right = Vector3::new(yaw.sin(), 0.0, -yaw.cos());
+        let back = Vector3::new(yaw.cos(), 0.0, yaw.sin());
+        // Drag the ground along with the cursor.
+        camera.target += (-right * dx as f32 + back * -dy as f32) * pixel_size;
+
+        let new_distance = (distance * self.zoom_step.powf(self.zoom_lines as f32))
+            .clamp(self.limits.min_distance, self.limits.max_distance);
+        // Scaling about the point under the cursor keeps it under the cursor. Orthographic
+        // views don't get smaller with distance, so they are scaled as well.
+        let scale = new_distance / distance;
+        if let Some(anchor) = zoom_anchor {
+            camera.target = anchor + (camera.target - anchor) * scale;
+        }
+        if let Projection::Orthographic { height } = &mut camera.projection {
+            *height *= scale;
+        }
+
+        let direction = Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        camera.eye = camera.target + direction * new_distance;
+
+        self.orbit_delta = (0.0, 0.0);
+        self.pan_delta = (0.0, 0.0);
+        self.zoom_lines = 0.0;
+        self.zoom_cursor = None;
+    }
+}
+
+/// Where the ray through `cursor` hits the horizontal plane through `camera.target`.
+fn ground_point_under(
+    camera: &Camera,
+    size: PhysicalSize<u32>,
+    cursor: PhysicalPosition<f64>,
+) -> Option<Point3<f32>> {
+    let x = (2.0 * cursor.x / size.width.max(1) as f64 - 1.0) as f32;
+    let y = (1.0 - 2.0 * cursor.y / size.height.max(1) as f64) as f32;
+    let (origin, direction) = camera.ray(x, y);
+
+    if direction.y >= 0.0 {
+        return None;
+    }
+    let t = (camera.target.y - origin.y) / direction.y;
+    Some(origin + direction * t)
+}
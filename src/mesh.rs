@@ -0,0 +1,439 @@
+use crate::{
+    err::Error,
+    light::Phong,
+    mipmap::MipmapGenerator,
+    model::normal_matrix,
+    texture::{TextureOptions, TextureState},
+    vertex::Vertex,
+};
+use bytemuck::cast_slice;
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3, Zero};
+use image::{
+    io::Reader as ImageReader, DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage,
+};
+use std::path::Path;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, Buffer,
+    BufferUsages, Device, IndexFormat, Queue,
+};
+
+/// Vertex and index buffers of one drawable part of a mesh asset.
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub num_indices: u32,
+    /// Index into `MeshAsset::materials`.
+    pub material: usize,
+}
+
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: TextureState,
+    pub phong: Phong,
+    pub bind_group: BindGroup,
+}
+
+/// Meshes and materials read from a glTF 2.0 (`.gltf`, `.glb`) or Wavefront OBJ file.
+///
+/// glTF meshes are placed where the nodes of the file's default scene put them: each node's
+/// transform is baked into the vertices, once per node using a mesh.
+pub struct MeshAsset {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Mesh {
+    pub const INDEX_FORMAT: IndexFormat = IndexFormat::Uint32;
+
+    fn new(
+        device: &Device,
+        name: String,
+        vertices: &[Vertex],
+        indices: &[u32],
+        material: usize,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{} vertex buffer", name)),
+            contents: cast_slice(vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{} index buffer", name)),
+            contents: cast_slice(indices),
+            usage: BufferUsages::INDEX,
+        });
+
+        Self {
+            name,
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            material,
+        }
+    }
+}
+
+impl Material {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        name: String,
+        diffuse_texture: TextureState,
+        phong: Phong,
+    ) -> Self {
+        let bind_group = Self::create_bind_group(device, layout, &diffuse_texture, phong, &name);
+
+        Self {
+            name,
+            diffuse_texture,
+            phong,
+            bind_group,
+        }
+    }
+
+    /// Binds `texture` and a uniform buffer holding `phong` with the texture bind group layout.
+    pub(crate) fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        texture: &TextureState,
+        phong: Phong,
+        label: &str,
+    ) -> BindGroup {
+        let phong_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(label),
+            contents: cast_slice(&[phong.to_uniform()]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        device.create_bind_group(&BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&texture.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: phong_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(label),
+        })
+    }
+}
+
+impl MeshAsset {
+    /// Loads `path`, picking the format from its extension. Base-color textures are bound
+    /// with `texture_bind_group_layout`; parts without one get a white texture. glTF materials
+    /// are shaded with `Phong::MATTE`, and parts without normals get smooth ones.
+    pub fn load(
+        device: &Device,
+        queue: &Queue,
+        mipmaps: &mut MipmapGenerator,
+        texture_bind_group_layout: &BindGroupLayout,
+        path: &Path,
+    ) -> Result<Self, Error> {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("gltf" | "glb") => {
+                Self::load_gltf(device, queue, mipmaps, texture_bind_group_layout, path)
+            }
+            Some("obj") => Self::load_obj(device, queue, mipmaps, texture_bind_group_layout, path),
+            _ => Err(Error::UnsupportedMeshFormat(path.to_path_buf())),
+        }
+    }
+
+    fn load_gltf(
+        device: &Device,
+        queue: &Queue,
+        mipmaps: &mut MipmapGenerator,
+        layout: &BindGroupLayout,
+        path: &Path,
+    ) -> Result<Self, Error> {
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|err| Error::GltfError(path.to_path_buf(), err))?;
+
+        let mut materials = document
+            .materials()
+            .map(|material| {
+                let name = material.name().map(str::to_owned).unwrap_or_else(|| {
+                    format!("{} material {:?}", path.display(), material.index())
+                });
+                let texture = match material.pbr_metallic_roughness().base_color_texture() {
+                    Some(info) => {
+                        let image = gltf_image(path, &images[info.texture().source().index()])?;
+                        TextureState::from_image(
+                            device,
+                            queue,
+                            mipmaps,
+                            &image,
+                            Some(&name),
+                            TextureOptions::mipmapped(),
+                        )?
+                    }
+                    None => white_texture(device, queue, mipmaps, &name)?,
+                };
+                Ok(Material::new(device, layout, name, texture, Phong::MATTE))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // Primitives without a material use a trailing default one.
+        let default_material = materials.len();
+        let mut needs_default_material = false;
+
+        // Files without a scene are shown as their meshes, untransformed.
+        let mut placed = Vec::new();
+        match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => {
+                for node in scene.nodes() {
+                    place_gltf_meshes(node, Matrix4::identity(), &mut placed);
+                }
+            }
+            None => placed.extend(document.meshes().map(|mesh| (mesh, Matrix4::identity()))),
+        }
+
+        let mut meshes = Vec::new();
+        for (mesh, world) in placed {
+            let normal_world = normal_matrix(world);
+            // Mirroring turns triangles around; their winding has to turn with them.
+            let mirrored = world.determinant() < 0.0;
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions = match reader.read_positions() {
+                    Some(positions) => positions,
+                    None => continue,
+                };
+                let mut vertices = positions
+                    .map(|position| {
+                        let position = world.transform_point(Point3::from(position));
+                        Vertex::new(position.into(), [0.0, 0.0], [0.0, 0.0, 0.0])
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(tex_coords) = reader.read_tex_coords(0) {
+                    for (vertex, tex_coords) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+                        vertex.set_tex_coords(tex_coords);
+                    }
+                }
+                let mut indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..vertices.len() as u32).collect::<Vec<_>>(),
+                };
+                if mirrored {
+                    for triangle in indices.chunks_exact_mut(3) {
+                        triangle.swap(1, 2);
+                    }
+                }
+                match reader.read_normals() {
+                    Some(normals) => {
+                        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                            let normal = normal_world * Vector3::from(normal);
+                            vertex.set_normal(normal.normalize().into());
+                        }
+                    }
+                    None => compute_normals(&mut vertices, &indices),
+                }
+                let material = primitive.material().index().unwrap_or_else(|| {
+                    needs_default_material = true;
+                    default_material
+                });
+                let name = format!(
+                    "{} primitive {}",
+                    mesh.name().unwrap_or("mesh"),
+                    primitive.index()
+                );
+                meshes.push(Mesh::new(device, name, &vertices, &indices, material));
+            }
+        }
+
+        if needs_default_material {
+            let name = format!("{} default material", path.display());
+            let texture = white_texture(device, queue, mipmaps, &name)?;
+            materials.push(Material::new(device, layout, name, texture, Phong::MATTE));
+        }
+
+        Ok(Self { meshes, materials })
+    }
+
+    fn load_obj(
+        device: &Device,
+        queue: &Queue,
+        mipmaps: &mut MipmapGenerator,
+        layout: &BindGroupLayout,
+        path: &Path,
+    ) -> Result<Self, Error> {
+        let (models, obj_materials) = tobj::load_obj(
+            path,
+            &tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|err| Error::ObjError(path.to_path_buf(), err))?;
+        let obj_materials =
+            obj_materials.map_err(|err| Error::ObjError(path.to_path_buf(), err))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut materials = obj_materials
+            .into_iter()
+            .map(|material| {
+                let texture = match &material.diffuse_texture {
+                    Some(file_name) => {
+                        let image_path = dir.join(file_name);
+                        let image = ImageReader::open(&image_path)
+                            .map_err(|err| Error::AssetIoError(image_path.clone(), err))?
+                            .decode()
+                            .map_err(|err| Error::AssetDecodeError(image_path, err))?;
+                        TextureState::from_image(
+                            device,
+                            queue,
+                            mipmaps,
+                            &image,
+                            Some(&material.name),
+                            TextureOptions::mipmapped(),
+                        )?
+                    }
+                    None => white_texture(device, queue, mipmaps, &material.name)?,
+                };
+                let phong = Phong::new(
+                    material.ambient.map_or(Phong::MATTE.ambient, average),
+                    material.specular.map_or(Phong::MATTE.specular, average),
+                    material.shininess.unwrap_or(Phong::MATTE.shininess),
+                );
+                Ok(Material::new(device, layout, material.name, texture, phong))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let default_material = materials.len();
+        let mut needs_default_material = false;
+
+        let meshes = models
+            .into_iter()
+            .map(|model| {
+                let mesh = model.mesh;
+                let mut vertices = mesh
+                    .positions
+                    .chunks_exact(3)
+                    .enumerate()
+                    .map(|(i, position)| {
+                        // OBJ puts the texture origin at the bottom left.
+                        let tex_coords = match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                            Some([u, v]) => [*u, 1.0 - *v],
+                            _ => [0.0, 0.0],
+                        };
+                        let normal = match mesh.normals.get(i * 3..i * 3 + 3) {
+                            Some([x, y, z]) => [*x, *y, *z],
+                            _ => [0.0, 0.0, 0.0],
+                        };
+                        Vertex::new([position[0], position[1], position[2]], tex_coords, normal)
+                    })
+                    .collect::<Vec<_>>();
+                if mesh.normals.is_empty() {
+                    compute_normals(&mut vertices, &mesh.indices);
+                }
+                let material = mesh.material_id.unwrap_or_else(|| {
+                    needs_default_material = true;
+                    default_material
+                });
+                Mesh::new(device, model.name, &vertices, &mesh.indices, material)
+            })
+            .collect::<Vec<_>>();
+
+        if needs_default_material {
+            let name = format!("{} default material", path.display());
+            let texture = white_texture(device, queue, mipmaps, &name)?;
+            materials.push(Material::new(device, layout, name, texture, Phong::MATTE));
+        }
+
+        Ok(Self { meshes, materials })
+    }
+}
+
+/// Collects the meshes of `node` and its descendants with their world transforms, given the
+/// world transform of `node`'s parent.
+fn place_gltf_meshes<'a>(
+    node: gltf::Node<'a>,
+    parent: Matrix4<f32>,
+    placed: &mut Vec<(gltf::Mesh<'a>, Matrix4<f32>)>,
+) {
+    let world = parent * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        placed.push((mesh, world));
+    }
+    for child in node.children() {
+        place_gltf_meshes(child, world, placed);
+    }
+}
+
+#[inline]
+fn average([r, g, b]: [f32; 3]) -> f32 {
+    (r + g + b) / 3.0
+}
+
+/// Sets each vertex normal to the area-weighted average of the faces that share it.
+fn compute_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vector3::zero(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(vertices[triangle[i] as usize].position()));
+        let face_normal = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += face_normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::unit_z()
+        };
+        vertex.set_normal(normal.into());
+    }
+}
+
+fn white_texture(
+    device: &Device,
+    queue: &Queue,
+    mipmaps: &mut MipmapGenerator,
+    label: &str,
+) -> Result<TextureState, Error> {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, [255; 4].into()));
+    TextureState::from_image(
+        device,
+        queue,
+        mipmaps,
+        &image,
+        Some(label),
+        TextureOptions::default(),
+    )
+}
+
+fn gltf_image(path: &Path, data: &gltf::image::Data) -> Result<DynamicImage, Error> {
+    use gltf::image::Format;
+
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let image = match data.format {
+        Format::R8 => GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => {
+            GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8)
+        }
+        Format::R8G8B8 => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => {
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        format => return Err(Error::UnsupportedImageFormat(path.to_path_buf(), format)),
+    };
+    image.ok_or_else(|| Error::ImageBufferSizeError(path.to_path_buf()))
+}
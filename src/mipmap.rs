@@ -0,0 +1,186 @@
+use std::{collections::HashMap, num::NonZeroU32};
+use wgpu::{
+    include_wgsl, AddressMode, BindGroupDescriptor, BindGroupEntry, BindingResource,
+    ColorTargetState, ColorWrites, CommandEncoderDescriptor, Device, Extent3d, FilterMode,
+    FragmentState, FrontFace, ImageCopyTexture, LoadOp, MultisampleState, Operations, Origin3d,
+    PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerDescriptor,
+    ShaderModule, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages, TextureViewDescriptor, VertexState,
+};
+
+/// Number of levels in a full mip chain down to 1x1.
+#[inline]
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Fills mip chains by blitting each level into the next one. The blit shader and sampler are
+/// created on first use and the pipeline once per texture format, then reused for every
+/// texture.
+#[derive(Default)]
+pub struct MipmapGenerator {
+    blit: Option<(ShaderModule, Sampler)>,
+    pipelines: HashMap<TextureFormat, RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills mip levels `1..mip_level_count` of `texture` by repeatedly blitting each level
+    /// into the next one with a linear filter. The texture needs `RENDER_ATTACHMENT` and
+    /// `COPY_SRC` usages and level 0 must already hold the image.
+    ///
+    /// Each source level is first copied into a single-level scratch texture, because the GL
+    /// backend can't sample views that start at a non-zero mip level.
+    pub fn generate(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        texture: &Texture,
+        size: Extent3d,
+        format: TextureFormat,
+        mip_level_count: u32,
+    ) {
+        if mip_level_count < 2 {
+            return;
+        }
+
+        let (shader, sampler) = self.blit.get_or_insert_with(|| {
+            let shader = device.create_shader_module(include_wgsl!("../resources/blit.wgsl"));
+            let sampler = device.create_sampler(&SamplerDescriptor {
+                label: Some("Mipmap blit sampler"),
+                address_mode_u: AddressMode::ClampToEdge,
+                address_mode_v: AddressMode::ClampToEdge,
+                address_mode_w: AddressMode::ClampToEdge,
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                mipmap_filter: FilterMode::Nearest,
+                ..Default::default()
+            });
+            (shader, sampler)
+        });
+        let pipeline = self
+            .pipelines
+            .entry(format)
+            .or_insert_with(|| create_blit_pipeline(device, shader, format));
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let target_views = (0..mip_level_count)
+            .map(|mip| {
+                texture.create_view(&TextureViewDescriptor {
+                    label: Some("Mipmap level view"),
+                    base_mip_level: mip,
+                    mip_level_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Mipmap encoder"),
+        });
+
+        for target_mip in 1..mip_level_count {
+            let source_size = size.mip_level_size(target_mip - 1, false);
+            let source = device.create_texture(&TextureDescriptor {
+                label: Some("Mipmap source texture"),
+                size: source_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            });
+            encoder.copy_texture_to_texture(
+                ImageCopyTexture {
+                    texture,
+                    mip_level: target_mip - 1,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyTexture {
+                    texture: &source,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                source_size,
+            );
+            let source_view = source.create_view(&TextureViewDescriptor::default());
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&source_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: Some("Mipmap blit bind group"),
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Mipmap blit pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target_views[target_mip as usize],
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+fn create_blit_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Mipmap blit pipeline"),
+        layout: None,
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -1,26 +1,131 @@
 use crate::mat4x4_uniform::Mat4x4Uniform;
-use bytemuck::{Pod, Zeroable};
-use cgmath::{Deg, Matrix4};
+use bytemuck::{cast_slice, Pod, Zeroable};
+use cgmath::{Matrix, Matrix3, Matrix4, One, Quaternion, SquareMatrix, Vector3};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages, Device,
+    Queue,
+};
 
+/// Placement of a mesh in the world: scale, then rotate, then translate.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Model {
-    rotate_over_x: f32,
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
 }
 
 impl Model {
+    #[inline]
+    pub fn new(position: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
     #[inline]
     pub fn build_model_matrix(&self) -> Matrix4<f32> {
-        Matrix4::from_angle_x(Deg(self.rotate_over_x))
+        Matrix4::from_translation(self.position)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Model {
+    #[inline]
+    fn default() -> Self {
+        Self::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Quaternion::one(),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
     }
 }
 
+/// Transforms normals by `matrix`: the inverse transpose of its upper 3x3, which keeps them
+/// perpendicular to surfaces under non-uniform scaling.
+pub fn normal_matrix(matrix: Matrix4<f32>) -> Matrix3<f32> {
+    let linear = Matrix3::from_cols(
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    );
+    // A scale of zero flattens the mesh, leaving no surface to light correctly anyway.
+    linear.invert().unwrap_or(linear).transpose()
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable, Default)]
-pub struct ModelUniform(Mat4x4Uniform);
+pub struct ModelUniform {
+    model: Mat4x4Uniform,
+    /// `normal_matrix(model)` laid out as a WGSL `mat3x3<f32>`, whose columns are padded to
+    /// 16 bytes.
+    normal: [[f32; 4]; 3],
+}
 
 impl ModelUniform {
     #[inline]
     pub fn update_model(&mut self, model: &Model) {
-        let Self(mat) = self;
-        mat.update_mat(model.build_model_matrix());
+        self.update_matrix(model.build_model_matrix());
+    }
+
+    #[inline]
+    pub fn update_matrix(&mut self, matrix: Matrix4<f32>) {
+        self.model.update_mat(matrix);
+        let normal = normal_matrix(matrix);
+        self.normal = [normal.x, normal.y, normal.z].map(|column| column.extend(0.0).into());
+    }
+}
+
+/// A model matrix uniform buffer and the bind group that pairs it with the camera.
+pub struct ModelState {
+    uniform: ModelUniform,
+    buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+impl ModelState {
+    pub fn new(
+        device: &Device,
+        camera_bind_group_layout: &BindGroupLayout,
+        camera_buffer: &Buffer,
+        label: &str,
+    ) -> Self {
+        let uniform = ModelUniform::default();
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(label),
+            contents: cast_slice(&[uniform]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(label),
+        });
+
+        Self {
+            uniform,
+            buffer,
+            bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn update_uniform(&mut self, queue: &Queue, matrix: Matrix4<f32>) {
+        self.uniform.update_matrix(matrix);
+        queue.write_buffer(&self.buffer, 0, cast_slice(&[self.uniform]));
     }
 }
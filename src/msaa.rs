@@ -0,0 +1,83 @@
+use crate::texture::TextureState;
+use wgpu::{
+    Adapter, Device, Extent3d, SurfaceConfiguration, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureFormatFeatureFlags, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub enum SampleCount {
+    #[default]
+    X1,
+    X2,
+    X4,
+    X8,
+}
+
+impl SampleCount {
+    #[inline]
+    pub const fn count(self) -> u32 {
+        match self {
+            Self::X1 => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+        }
+    }
+
+    /// The highest sample count not above `self` that wgpu guarantees for multisampled
+    /// formats: 1 or 4. wgpu 0.13 can't tell which other counts an adapter supports, since its
+    /// `MULTISAMPLE` format flag doesn't name any.
+    #[inline]
+    pub const fn guaranteed(self) -> Self {
+        match self {
+            Self::X1 | Self::X2 => Self::X1,
+            Self::X4 | Self::X8 => Self::X4,
+        }
+    }
+
+    /// Returns `guaranteed`, or `X1` when the adapter can't multisample and resolve `format`
+    /// together with the depth buffer at all.
+    pub fn supported(self, adapter: &Adapter, format: TextureFormat) -> Self {
+        let color = adapter.get_texture_format_features(format).flags;
+        let depth = adapter
+            .get_texture_format_features(TextureState::DEPTH_FORMAT)
+            .flags;
+        let multisampled = color.contains(
+            TextureFormatFeatureFlags::MULTISAMPLE | TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE,
+        ) && depth.contains(TextureFormatFeatureFlags::MULTISAMPLE);
+
+        if multisampled {
+            self.guaranteed()
+        } else {
+            Self::X1
+        }
+    }
+}
+
+/// Creates the multisampled color target that is resolved into the frame view,
+/// or `None` when multisampling is off.
+pub fn create_msaa_view(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    sample_count: SampleCount,
+) -> Option<TextureView> {
+    if sample_count == SampleCount::X1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Multisampled color texture"),
+        size: Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: sample_count.count(),
+        dimension: TextureDimension::D2,
+        format: config.format,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+    });
+
+    Some(texture.create_view(&TextureViewDescriptor::default()))
+}
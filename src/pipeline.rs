@@ -1,74 +1,354 @@
-use crate::vertex::Vertex;
+use crate::{
+    assets::{AssetManager, ShaderHandle},
+    err::Error,
+    instance::MeshInstanceRaw,
+    msaa::SampleCount,
+    text::TextVertex,
+    texture::TextureState,
+    vertex::Vertex,
+};
 use wgpu::{
-    include_wgsl, BindGroupLayout, BlendState, ColorTargetState, ColorWrites, Device, Face,
-    FragmentState, FrontFace, MultisampleState, PipelineLayout, PipelineLayoutDescriptor,
-    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
-    ShaderModule, SurfaceConfiguration, VertexBufferLayout, VertexState,
+    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
+    DepthStencilState, Device, Face, FragmentState, FrontFace, MultisampleState, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, StencilState, SurfaceConfiguration, VertexBufferLayout,
+    VertexState,
 };
 
-pub fn create_pipeline<'a>(
+#[derive(Copy, Clone)]
+pub struct DepthConfig {
+    pub compare: CompareFunction,
+    pub write_enabled: bool,
+}
+
+impl DepthConfig {
+    /// Regular depth testing for opaque geometry.
+    pub const OPAQUE: Self = Self {
+        compare: CompareFunction::Less,
+        write_enabled: true,
+    };
+
+    /// Draws over everything and leaves the depth buffer untouched, e.g. for screen-space passes.
+    pub const OVERLAY: Self = Self {
+        compare: CompareFunction::Always,
+        write_enabled: false,
+    };
+}
+
+/// Shaders the scene pipelines are built from.
+#[derive(Copy, Clone)]
+pub struct Shaders {
+    pub shader: ShaderHandle,
+    pub challenge: ShaderHandle,
+    pub challenge4: ShaderHandle,
+    pub shadow: ShaderHandle,
+    pub text: ShaderHandle,
+}
+
+impl Shaders {
+    /// Loads the shaders from the root of `assets`.
+    pub fn load(device: &Device, assets: &mut AssetManager) -> Result<Self, Error> {
+        Ok(Self {
+            shader: assets.load_shader(device, "shader.wgsl")?,
+            challenge: assets.load_shader(device, "challenge.wgsl")?,
+            challenge4: assets.load_shader(device, "challenge4.wgsl")?,
+            shadow: assets.load_shader(device, "shadow.wgsl")?,
+            text: assets.load_shader(device, "text.wgsl")?,
+        })
+    }
+}
+
+pub struct Pipelines {
+    pub render: RenderPipeline,
+    pub challenge: RenderPipeline,
+    /// Draws the challenge 4 node with `challenge4.wgsl`, which shares the scene layouts.
+    pub challenge4: RenderPipeline,
+    /// Depth-only pass from the directional light into the shadow map.
+    pub shadow: RenderPipeline,
+    /// Alpha-blended glyph quads drawn over the scene.
+    pub text: RenderPipeline,
+}
+
+impl Pipelines {
+    #[inline]
+    pub fn get_mut(&mut self, kind: PipelineKind) -> &mut RenderPipeline {
+        match kind {
+            PipelineKind::Render => &mut self.render,
+            PipelineKind::Challenge => &mut self.challenge,
+            PipelineKind::Challenge4 => &mut self.challenge4,
+            PipelineKind::Shadow => &mut self.shadow,
+            PipelineKind::Text => &mut self.text,
+        }
+    }
+}
+
+/// One of the `Pipelines`, each built from a single shader.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PipelineKind {
+    Render,
+    Challenge,
+    Challenge4,
+    Shadow,
+    Text,
+}
+
+impl PipelineKind {
+    pub const ALL: [Self; 5] = [
+        Self::Render,
+        Self::Challenge,
+        Self::Challenge4,
+        Self::Shadow,
+        Self::Text,
+    ];
+}
+
+impl Shaders {
+    /// The shader the `kind` pipeline is built from.
+    #[inline]
+    pub fn get(&self, kind: PipelineKind) -> ShaderHandle {
+        match kind {
+            PipelineKind::Render => self.shader,
+            PipelineKind::Challenge => self.challenge,
+            PipelineKind::Challenge4 => self.challenge4,
+            PipelineKind::Shadow => self.shadow,
+            PipelineKind::Text => self.text,
+        }
+    }
+
+    /// The pipelines built from `shader`, which have to be rebuilt when it changes.
+    pub fn pipelines_using(&self, shader: ShaderHandle) -> impl Iterator<Item = PipelineKind> + '_ {
+        PipelineKind::ALL
+            .into_iter()
+            .filter(move |kind| self.get(*kind) == shader)
+    }
+}
+
+/// Bind group layouts of each kind of pipeline, in group order.
+pub struct BindGroupLayouts<'a> {
+    pub scene: &'a [&'a BindGroupLayout],
+    pub shadow: &'a [&'a BindGroupLayout],
+    pub text: &'a [&'a BindGroupLayout],
+}
+
+pub fn create_pipeline(
     device: &Device,
     config: &SurfaceConfiguration,
-    bind_group_layouts: &'a [&'a BindGroupLayout],
-) -> (RenderPipeline, RenderPipeline, RenderPipeline) {
-    let shader = device.create_shader_module(include_wgsl!("../resources/shader.wgsl"));
-    let challenge_shader =
-        device.create_shader_module(include_wgsl!("../resources/challenge.wgsl"));
-    let challenge4_shader =
-        device.create_shader_module(include_wgsl!("../resources/challenge4.wgsl"));
-
-    let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-        label: Some("Render Pipeline Layout"),
-        bind_group_layouts,
-        push_constant_ranges: &[],
-    });
-
-    let challenge_render_pipeline_layout =
-        device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Challenge Render Pipeline Layout"),
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
-
-    let challenge4_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-        label: Some("Challenge4 Pipeline Layout"),
-        bind_group_layouts,
-        push_constant_ranges: &[],
-    });
-
-    let render_pipeline = create_pipeline_int(
-        device,
-        config,
-        &render_pipeline_layout,
-        &shader,
-        &[Vertex::desc()],
-    );
-
-    let challenge_pipeline = create_pipeline_int(
-        device,
-        config,
-        &challenge_render_pipeline_layout,
-        &challenge_shader,
-        &[],
-    );
-
-    let challenge4_pipeline = create_pipeline_int(
-        device,
-        config,
-        &challenge4_pipeline_layout,
-        &shader,
-        &[Vertex::desc()],
-    );
-
-    (render_pipeline, challenge_pipeline, challenge4_pipeline)
+    sample_count: SampleCount,
+    bind_group_layouts: &BindGroupLayouts,
+    shaders: &Shaders,
+    assets: &AssetManager,
+) -> Pipelines {
+    let create = |kind| {
+        create_pipeline_of(
+            kind,
+            device,
+            config,
+            sample_count,
+            bind_group_layouts,
+            shaders,
+            assets,
+        )
+    };
+    Pipelines {
+        render: create(PipelineKind::Render),
+        challenge: create(PipelineKind::Challenge),
+        challenge4: create(PipelineKind::Challenge4),
+        shadow: create(PipelineKind::Shadow),
+        text: create(PipelineKind::Text),
+    }
+}
+
+/// Builds the `kind` pipeline from its shader in `shaders`.
+pub fn create_pipeline_of(
+    kind: PipelineKind,
+    device: &Device,
+    config: &SurfaceConfiguration,
+    sample_count: SampleCount,
+    bind_group_layouts: &BindGroupLayouts,
+    shaders: &Shaders,
+    assets: &AssetManager,
+) -> RenderPipeline {
+    let shader = assets.shader(shaders.get(kind));
+    match kind {
+        PipelineKind::Render => {
+            let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: bind_group_layouts.scene,
+                push_constant_ranges: &[],
+            });
+            create_pipeline_int(
+                device,
+                config,
+                sample_count,
+                &render_pipeline_layout,
+                shader,
+                &[Vertex::desc(), MeshInstanceRaw::desc()],
+                DepthConfig::OPAQUE,
+            )
+        }
+        PipelineKind::Challenge => {
+            let challenge_render_pipeline_layout =
+                device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Challenge Render Pipeline Layout"),
+                    bind_group_layouts: &[],
+                    push_constant_ranges: &[],
+                });
+            create_pipeline_int(
+                device,
+                config,
+                sample_count,
+                &challenge_render_pipeline_layout,
+                shader,
+                &[],
+                DepthConfig::OVERLAY,
+            )
+        }
+        PipelineKind::Challenge4 => {
+            let challenge4_pipeline_layout =
+                device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Challenge4 Pipeline Layout"),
+                    bind_group_layouts: bind_group_layouts.scene,
+                    push_constant_ranges: &[],
+                });
+            create_pipeline_int(
+                device,
+                config,
+                sample_count,
+                &challenge4_pipeline_layout,
+                shader,
+                &[Vertex::desc(), MeshInstanceRaw::desc()],
+                DepthConfig::OPAQUE,
+            )
+        }
+        PipelineKind::Shadow => {
+            let shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: bind_group_layouts.shadow,
+                push_constant_ranges: &[],
+            });
+            create_shadow_pipeline(device, &shadow_pipeline_layout, shader)
+        }
+        PipelineKind::Text => {
+            let text_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Text Pipeline Layout"),
+                bind_group_layouts: bind_group_layouts.text,
+                push_constant_ranges: &[],
+            });
+            create_overlay_pipeline(
+                device,
+                config,
+                sample_count,
+                "Text pipeline",
+                &text_pipeline_layout,
+                shader,
+                &[TextVertex::desc()],
+            )
+        }
+    }
+}
+
+/// Alpha-blended, unculled 2D geometry drawn over the scene without depth testing.
+fn create_overlay_pipeline(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    sample_count: SampleCount,
+    label: &str,
+    pipeline_layout: &PipelineLayout,
+    shader: &ShaderModule,
+    buffers: &[VertexBufferLayout],
+) -> RenderPipeline {
+    let depth = DepthConfig::OVERLAY;
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(pipeline_layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers,
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: config.format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureState::DEPTH_FORMAT,
+            depth_write_enabled: depth.write_enabled,
+            depth_compare: depth.compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: sample_count.count(),
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+fn create_shadow_pipeline(
+    device: &Device,
+    pipeline_layout: &PipelineLayout,
+    shader: &ShaderModule,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Shadow pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), MeshInstanceRaw::desc()],
+        },
+        fragment: None,
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            // Single-sided geometry like the pentagon has to cast shadows from both sides.
+            cull_mode: None,
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureState::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: StencilState::default(),
+            // Pushes occluders back a little to keep lit surfaces from shadowing themselves.
+            bias: DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
 }
 
 fn create_pipeline_int<'a>(
     device: &Device,
     config: &SurfaceConfiguration,
+    sample_count: SampleCount,
     pipeline_layout: &PipelineLayout,
     shader: &ShaderModule,
     buffers: &'a [VertexBufferLayout<'a>],
+    depth: DepthConfig,
 ) -> RenderPipeline {
     device.create_render_pipeline(&RenderPipelineDescriptor {
         label: Some("Render pipeline"),
@@ -96,9 +376,15 @@ fn create_pipeline_int<'a>(
             unclipped_depth: false,
             conservative: false,
         },
-        depth_stencil: None,
+        depth_stencil: Some(DepthStencilState {
+            format: TextureState::DEPTH_FORMAT,
+            depth_write_enabled: depth.write_enabled,
+            depth_compare: depth.compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
         multisample: MultisampleState {
-            count: 1,
+            count: sample_count.count(),
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -0,0 +1,279 @@
+use crate::{
+    camera::{Camera, Projection},
+    camera_controller::CameraMode,
+    challenge::Challenge,
+    clock::FrameTime,
+    err::Error,
+    input_map::{Action, Binding, InputMap},
+    state::State,
+};
+use cgmath::Point3;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        TouchPhase, WindowEvent,
+    },
+};
+
+/// The parts of a window or device event that `State` reacts to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum RecordedEvent {
+    Resized(PhysicalSize<u32>),
+    Focused(bool),
+    Keyboard(KeyboardInput),
+    ReceivedCharacter(char),
+    ModifiersChanged(ModifiersState),
+    CursorMoved(PhysicalPosition<f64>),
+    CursorEntered,
+    CursorLeft,
+    MouseWheel(MouseScrollDelta),
+    MouseInput(ElementState, MouseButton),
+    /// Raw mouse motion, as passed to `State::mouse_motion`.
+    MouseMotion(f64, f64),
+}
+
+impl RecordedEvent {
+    /// The recordable part of `event`, if any.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) => Self::Resized(*size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                Self::Resized(**new_inner_size)
+            }
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            WindowEvent::KeyboardInput { input, .. } => Self::Keyboard(*input),
+            WindowEvent::ReceivedCharacter(c) => Self::ReceivedCharacter(*c),
+            WindowEvent::ModifiersChanged(modifiers) => Self::ModifiersChanged(*modifiers),
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved(*position),
+            WindowEvent::CursorEntered { .. } => Self::CursorEntered,
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel(*delta),
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseInput(*state, *button),
+            _ => return None,
+        })
+    }
+
+    /// The window event this was recorded from, or `None` for raw mouse motion.
+    #[allow(deprecated)]
+    pub fn to_window_event(&self) -> Option<WindowEvent<'static>> {
+        // Recordings don't keep track of devices.
+        let device_id = unsafe { DeviceId::dummy() };
+        Some(match *self {
+            Self::Resized(size) => WindowEvent::Resized(size),
+            Self::Focused(focused) => WindowEvent::Focused(focused),
+            Self::Keyboard(input) => WindowEvent::KeyboardInput {
+                device_id,
+                input,
+                is_synthetic: false,
+            },
+            Self::ReceivedCharacter(c) => WindowEvent::ReceivedCharacter(c),
+            Self::ModifiersChanged(modifiers) => WindowEvent::ModifiersChanged(modifiers),
+            Self::CursorMoved(position) => WindowEvent::CursorMoved {
+                device_id,
+                position,
+                modifiers: ModifiersState::empty(),
+            },
+            Self::CursorEntered => WindowEvent::CursorEntered { device_id },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseWheel(delta) => WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            },
+            Self::MouseInput(state, button) => WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                modifiers: ModifiersState::empty(),
+            },
+            Self::MouseMotion(..) => return None,
+        })
+    }
+}
+
+/// What a recording starts from, written on its first line: a replay puts the `State` it
+/// plays into in the same state before the first frame.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordingHeader {
+    pub size: PhysicalSize<u32>,
+    /// Bindings of the active `InputMap`.
+    pub bindings: HashMap<Action, Vec<Binding>>,
+    pub camera_mode: CameraMode,
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub projection: Projection,
+    pub challenge: Challenge,
+    pub time_scale: f32,
+    pub paused: bool,
+}
+
+impl RecordingHeader {
+    /// The parts of `state` that decide how it reacts to input.
+    pub fn from_state(state: &State) -> Self {
+        let camera = state.camera();
+        Self {
+            size: state.get_size(),
+            bindings: state.input_map().bindings().clone(),
+            camera_mode: state.camera_mode(),
+            eye: camera.eye,
+            target: camera.target,
+            projection: camera.projection,
+            challenge: state.challenge(),
+            time_scale: state.clock().scale(),
+            paused: state.clock().is_paused(),
+        }
+    }
+
+    /// Puts `state` back the way it was when the recording started.
+    pub fn apply(&self, state: &mut State) {
+        state.resize(self.size);
+        state.set_input_map(InputMap::new(self.bindings.clone()));
+        state.set_camera_mode(self.camera_mode);
+        state.set_camera(Camera {
+            eye: self.eye,
+            target: self.target,
+            projection: self.projection,
+            ..*state.camera()
+        });
+        state.set_challenge(self.challenge);
+        state.clock_mut().set_scale(self.time_scale);
+        state.clock_mut().set_paused(self.paused);
+    }
+}
+
+/// The input of one frame, followed by the real time the frame advanced the clock by.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct RecordedFrame {
+    pub events: Vec<RecordedEvent>,
+    pub delta: Duration,
+}
+
+/// Writes input to a file as it happens, a RON `RecordingHeader` followed by one
+/// `RecordedFrame` per line, so a recording survives a crash up to the last finished frame.
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    frame: RecordedFrame,
+}
+
+impl Recorder {
+    /// Starts a recording from the state described by `header`.
+    pub fn create(path: impl Into<PathBuf>, header: &RecordingHeader) -> Result<Self, Error> {
+        let path = path.into();
+        let file = File::create(&path).map_err(|err| Error::RecordingIoError(path.clone(), err))?;
+        let mut recorder = Self {
+            path,
+            writer: BufWriter::new(file),
+            frame: RecordedFrame::default(),
+        };
+        let line = ron::to_string(header)
+            .map_err(|err| Error::RecordingWriteError(recorder.path.clone(), err))?;
+        recorder.write_line(&line)?;
+        Ok(recorder)
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn record(&mut self, event: RecordedEvent) {
+        self.frame.events.push(event);
+    }
+
+    /// Writes the events since the last frame along with the frame's `delta`.
+    pub fn finish_frame(&mut self, delta: Duration) -> Result<(), Error> {
+        self.frame.delta = delta;
+        let line = ron::to_string(&self.frame)
+            .map_err(|err| Error::RecordingWriteError(self.path.clone(), err))?;
+        self.write_line(&line)?;
+        self.frame.events.clear();
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        writeln!(self.writer, "{}", line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| Error::RecordingIoError(self.path.clone(), err))
+    }
+}
+
+/// Feeds a recording back into a `State`, frame by frame, the way `event_loop::run` would.
+pub struct Replay {
+    /// Applied before the first frame is played.
+    header: Option<RecordingHeader>,
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl Replay {
+    #[inline]
+    pub fn new(header: RecordingHeader, frames: impl IntoIterator<Item = RecordedFrame>) -> Self {
+        Self {
+            header: Some(header),
+            frames: frames.into_iter().collect(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| Error::RecordingIoError(path.to_owned(), err))?;
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| (index + 1, line));
+        let parse_error = |line, err| Error::RecordingParseError(path.to_owned(), line, err);
+        // An empty recording fails to parse as a header.
+        let (line, header) = lines.next().unwrap_or((1, ""));
+        let header = ron::from_str(header).map_err(|err| parse_error(line, err))?;
+        let frames = lines
+            .map(|(line, frame)| ron::from_str(frame).map_err(|err| parse_error(line, err)))
+            .collect::<Result<Vec<RecordedFrame>, _>>()?;
+        Ok(Self::new(header, frames))
+    }
+
+    /// Number of frames left to play.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Plays the next frame's input into `state` and advances its clock, or returns `None` at
+    /// the end of the recording. Doesn't render.
+    pub fn play_frame(&mut self, state: &mut State) -> Option<FrameTime> {
+        let frame = self.frames.pop_front()?;
+        if let Some(header) = self.header.take() {
+            header.apply(state);
+        }
+        for event in &frame.events {
+            if let RecordedEvent::MouseMotion(dx, dy) = *event {
+                state.mouse_motion((dx, dy));
+            } else if let Some(event) = event.to_window_event() {
+                state.handle_window_event(&event);
+            }
+        }
+        Some(state.advance(frame.delta))
+    }
+
+    /// Plays all remaining frames into `state`.
+    pub fn play_to_end(&mut self, state: &mut State) {
+        while self.play_frame(state).is_some() {}
+    }
+}
@@ -0,0 +1,210 @@
+use crate::err::Error;
+use image::RgbaImage;
+use std::{num::NonZeroU32, sync::mpsc};
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Surface,
+    SurfaceConfiguration, SurfaceTexture, Texture, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureView, TextureViewDescriptor,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+pub enum RenderTarget {
+    Surface(Surface),
+    Offscreen(OffscreenTarget),
+}
+
+pub enum Frame<'a> {
+    Surface(SurfaceTexture, TextureView),
+    Offscreen(&'a OffscreenTarget),
+}
+
+pub struct OffscreenTarget {
+    texture: Texture,
+    view: TextureView,
+}
+
+impl RenderTarget {
+    pub fn configure(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        match self {
+            Self::Surface(surface) => surface.configure(device, config),
+            Self::Offscreen(offscreen) => *offscreen = OffscreenTarget::new(device, config),
+        }
+    }
+
+    pub fn acquire(&self) -> Result<Frame<'_>, Error> {
+        Ok(match self {
+            Self::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&TextureViewDescriptor::default());
+                Frame::Surface(output, view)
+            }
+            Self::Offscreen(offscreen) => Frame::Offscreen(offscreen),
+        })
+    }
+}
+
+impl Frame<'_> {
+    #[inline]
+    pub fn view(&self) -> &TextureView {
+        match self {
+            Self::Surface(_, view) => view,
+            Self::Offscreen(offscreen) => &offscreen.view,
+        }
+    }
+
+    /// Reads the frame back once it has been rendered. Surfaces have to be configured with
+    /// `TextureUsages::COPY_SRC` for this.
+    pub fn read_image(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        config: &SurfaceConfiguration,
+    ) -> Result<RgbaImage, Error> {
+        match self {
+            Self::Surface(output, _) => read_texture(
+                device,
+                queue,
+                &output.texture,
+                config.format,
+                config.width,
+                config.height,
+            ),
+            Self::Offscreen(offscreen) => offscreen.read_image(device, queue, config),
+        }
+    }
+
+    #[inline]
+    pub fn present(self) {
+        if let Self::Surface(output, _) = self {
+            output.present();
+        }
+    }
+}
+
+impl OffscreenTarget {
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(device: &Device, config: &SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen target texture"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    #[inline]
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn read_image(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        config: &SurfaceConfiguration,
+    ) -> Result<RgbaImage, Error> {
+        read_texture(
+            device,
+            queue,
+            &self.texture,
+            config.format,
+            config.width,
+            config.height,
+        )
+    }
+}
+
+/// Copies the first mip level of a 4-bytes-per-texel 2D texture into an RGBA image,
+/// stripping the row padding required by `COPY_BYTES_PER_ROW_ALIGNMENT` and swapping the
+/// channels of BGRA formats.
+pub fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage, Error> {
+    let unpadded_bytes_per_row = 4 * width;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+        * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Readback buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: NonZeroU32::new(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|_| Error::WgpuBufferAsyncError)?
+        .map_err(|_| Error::WgpuBufferAsyncError)?;
+
+    let mut pixels = {
+        let data = slice.get_mapped_range();
+        data.chunks_exact(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    buffer.unmap();
+    bgra_to_rgba(format, &mut pixels);
+
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("one RGBA texel per pixel"))
+}
+
+/// Reorders the texels of `pixels` from `format` to RGBA if it is one of the BGRA formats,
+/// and leaves them alone otherwise.
+pub fn bgra_to_rgba(format: TextureFormat, pixels: &mut [u8]) {
+    if let TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb = format {
+        for texel in pixels.chunks_exact_mut(4) {
+            texel.swap(0, 2);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    assets::{MeshHandle, TextureHandle},
+    light::Phong,
+    model::Model,
+};
+use cgmath::{Matrix4, SquareMatrix};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    #[inline]
+    pub(crate) const fn index(self) -> usize {
+        let Self(index) = self;
+        index
+    }
+}
+
+/// Geometry drawn at a node, once per instance of the node or once if it has none.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NodeMesh {
+    /// The built-in pentagon from `vertex::VERTICES` and `vertex::INDICES`.
+    Pentagon,
+    /// The built-in pentagon drawn with `vertex::INDICES_CHALLENGE2`.
+    PentagonChallenge2,
+    /// Triangle generated in clip space by `challenge.wgsl`; ignores transforms and materials.
+    ClipTriangle,
+    Asset(MeshHandle),
+}
+
+/// Shader a node's mesh is drawn with. Both read the node's model transform at group 1
+/// binding 1.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum MaterialShader {
+    /// `shader.wgsl`.
+    #[default]
+    Standard,
+    /// `challenge4.wgsl`.
+    Challenge4,
+}
+
+/// Texture, shading and shader a node's mesh is drawn with. Overrides the materials of mesh
+/// assets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NodeMaterial {
+    pub texture: TextureHandle,
+    pub phong: Phong,
+    pub shader: MaterialShader,
+}
+
+impl NodeMaterial {
+    #[inline]
+    pub fn new(texture: TextureHandle, shader: MaterialShader) -> Self {
+        Self {
+            texture,
+            phong: Phong::MATTE,
+            shader,
+        }
+    }
+
+    #[inline]
+    pub fn with_phong(self, phong: Phong) -> Self {
+        Self { phong, ..self }
+    }
+}
+
+pub struct Node {
+    pub name: String,
+    /// Placement relative to the parent node.
+    pub transform: Model,
+    pub mesh: Option<NodeMesh>,
+    pub material: Option<NodeMaterial>,
+    /// Hidden nodes are skipped together with their children.
+    pub visible: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f32>,
+}
+
+impl Node {
+    pub fn new(name: impl Into<String>, transform: Model) -> Self {
+        Self {
+            name: name.into(),
+            transform,
+            mesh: None,
+            material: None,
+            visible: true,
+            parent: None,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+        }
+    }
+
+    #[inline]
+    pub fn with_mesh(self, mesh: NodeMesh) -> Self {
+        Self {
+            mesh: Some(mesh),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn with_material(self, material: NodeMaterial) -> Self {
+        Self {
+            material: Some(material),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn with_visible(self, visible: bool) -> Self {
+        Self { visible, ..self }
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    #[inline]
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Transform from this node's space to world space as of the last
+    /// `Scene::update_world_matrices`.
+    #[inline]
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world
+    }
+}
+
+/// Tree of nodes whose transforms are relative to their parents.
+///
+/// Nodes can only be attached to nodes that already exist, so a parent always comes before
+/// its children in `nodes`.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `node` as the last child of `parent`, or as a new root.
+    pub fn add(&mut self, parent: Option<NodeId>, mut node: Node) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(node);
+        id
+    }
+
+    #[inline]
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.index()]
+    }
+
+    #[inline]
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.index()]
+    }
+
+    #[inline]
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (NodeId(index), node))
+    }
+
+    pub fn update_world_matrices(&mut self) {
+        for index in 0..self.nodes.len() {
+            let local = self.nodes[index].transform.build_model_matrix();
+            self.nodes[index].world = match self.nodes[index].parent {
+                Some(parent) => self.nodes[parent.index()].world * local,
+                None => local,
+            };
+        }
+    }
+
+    /// Visible nodes in draw order: depth first, parents before their children.
+    pub fn visible_nodes(&self) -> Vec<NodeId> {
+        let mut visible = Vec::new();
+        let mut stack = self.roots.iter().rev().copied().collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if node.visible {
+                visible.push(id);
+                stack.extend(node.children.iter().rev());
+            }
+        }
+        visible
+    }
+}
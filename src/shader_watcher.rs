@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Polls a directory for WGSL files whose modification time or length changed since the last
+/// poll. Checking the length too catches most edits made within the file system's timestamp
+/// granularity.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: HashMap<String, (SystemTime, u64)>,
+    /// Real time passed since the last poll.
+    since_poll: Duration,
+}
+
+impl ShaderWatcher {
+    /// Real time between polls driven by `is_due`, short enough for edits to show up
+    /// promptly without listing the directory every frame.
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let mut watcher = Self {
+            dir: dir.into(),
+            modified: HashMap::new(),
+            since_poll: Duration::ZERO,
+        };
+        watcher.poll();
+        watcher
+    }
+
+    /// Counts `real` time towards the next poll and returns whether `POLL_INTERVAL` has
+    /// passed since the last one.
+    pub fn is_due(&mut self, real: Duration) -> bool {
+        self.since_poll += real;
+        self.since_poll >= Self::POLL_INTERVAL
+    }
+
+    /// Returns the file names of shaders that were added or changed.
+    pub fn poll(&mut self) -> Vec<String> {
+        self.since_poll = Duration::ZERO;
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::warn!("can't watch {}: {}", self.dir.display(), err);
+                return Vec::new();
+            }
+        };
+
+        let mut changed = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension() != Some(OsStr::new("wgsl")) {
+                continue;
+            }
+            let (file_name, modified) = match (
+                path.file_name().and_then(OsStr::to_str),
+                entry
+                    .metadata()
+                    .and_then(|metadata| Ok((metadata.modified()?, metadata.len()))),
+            ) {
+                (Some(file_name), Ok(modified)) => (file_name.to_owned(), modified),
+                _ => continue,
+            };
+            if self.modified.insert(file_name.clone(), modified) != Some(modified) {
+                changed.push(file_name);
+            }
+        }
+        changed
+    }
+}
@@ -1,64 +1,214 @@
-use crate::model::{Model, ModelUniform};
 use crate::{
-    camera::{Camera, CameraUniform},
-    camera_controller::CameraController,
+    assets::{AssetManager, MeshHandle, TextureHandle},
+    camera::{Camera, CameraUniform, Projection, ProjectionTransition},
+    camera_controller::{CameraController, CameraMode},
     challenge::{Challenge, ChallengeEnum},
+    clock::{Clock, FrameTime},
+    debug_ui::{DebugUi, DebugUiTargets},
     err::Error,
-    pipeline::create_pipeline,
-    texture::TextureState,
+    frame_capture::FrameCapture,
+    input_map::{Action, InputMap},
+    instance::{InstanceBuffer, MeshInstance},
+    light::{Lights, LightsState, Phong},
+    map_camera_controller::{MapCameraController, MapCameraLimits},
+    mesh::{Material, Mesh},
+    model::{Model, ModelState},
+    msaa::{create_msaa_view, SampleCount},
+    pipeline::{
+        create_pipeline, create_pipeline_of, BindGroupLayouts, PipelineKind, Pipelines, Shaders,
+    },
+    recording::{RecordedEvent, Recorder, RecordingHeader},
+    render_target::{OffscreenTarget, RenderTarget},
+    scene::{MaterialShader, Node, NodeId, NodeMaterial, NodeMesh, Scene},
+    shader_watcher::ShaderWatcher,
+    text::{TextRenderer, TextSection},
+    texture::{TextureOptions, TextureState},
     vertex::{INDICES, INDICES_CHALLENGE2, VERTICES},
 };
 use bytemuck::cast_slice;
-use cgmath::Vector3;
-use image::ImageFormat;
-use std::iter;
+use cgmath::{Deg, Quaternion, Rotation3, Vector3};
+use image::{ImageFormat, ImageOutputFormat, RgbaImage};
+use imgui::Io;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter},
+    iter, mem,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
-    Color, CommandEncoderDescriptor, Device, DeviceDescriptor, Features, IndexFormat, Instance,
-    Limits, LoadOp, Operations, PowerPreference, PresentMode, Queue, RenderPassColorAttachment,
-    RenderPassDescriptor, RenderPipeline, RequestAdapterOptions, SamplerBindingType, ShaderStages,
-    Surface, SurfaceConfiguration, TextureSampleType, TextureUsages, TextureViewDescriptor,
-    TextureViewDimension,
+    Adapter, Backends, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingType, Buffer, BufferBindingType, BufferUsages, Color, CommandEncoder,
+    CommandEncoderDescriptor, Device, DeviceDescriptor, ErrorFilter, Features, IndexFormat,
+    Instance, Limits, LoadOp, Operations, PowerPreference, PresentMode, Queue,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipeline, RequestAdapterOptions, SamplerBindingType, ShaderStages, SurfaceConfiguration,
+    TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDimension,
 };
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, WindowEvent},
     window::Window,
 };
 
 pub struct State {
-    surface: Surface,
+    target: RenderTarget,
+    adapter: Adapter,
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
     size: PhysicalSize<u32>,
     clear_color: Color,
-    render_pipeline: RenderPipeline,
-    challenge_pipeline: RenderPipeline,
+    sample_count: SampleCount,
+    msaa_view: Option<TextureView>,
+    texture_bind_group_layout: BindGroupLayout,
+    camera_bind_group_layout: BindGroupLayout,
+    shaders: Shaders,
+    shader_watcher: Option<ShaderWatcher>,
+    pipelines: Pipelines,
     challenge: Challenge,
+    challenge_nodes: ChallengeNodes,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
     num_indices: u32,
     index_buffer_challenge2: Buffer,
     num_indices_challenge2: u32,
-    diffuse_bind_group: BindGroup,
-    challenge3_bind_group: BindGroup,
-    _diffuse_texture: TextureState,
+    assets: AssetManager,
+    default_texture: TextureHandle,
+    material_bind_groups: HashMap<(TextureHandle, Phong), BindGroup>,
+    depth_texture: TextureState,
     camera: Camera,
+    /// The camera before the last simulation step, blended with `camera` for rendering.
+    previous_camera: Camera,
+    /// How far rendering is from `previous_camera` to `camera`.
+    render_alpha: f32,
+    /// Animates the rendered camera after `camera` switched projections.
+    projection_transition: Option<ProjectionTransition>,
     camera_uniform: CameraUniform,
     camera_buffer: Buffer,
-    camera_bind_group: BindGroup,
     camera_controller: CameraController,
-    model: Model,
-    model_uniform: ModelUniform,
-    model_buffer: Buffer,
-    model_bind_group: BindGroup,
+    map_camera_controller: MapCameraController,
+    cursor_grabbed: bool,
+    input_map: InputMap,
+    quit_requested: bool,
+    recorder: Option<Recorder>,
+    screenshot_requested: bool,
+    screenshot_dir: PathBuf,
+    frame_capture: Option<FrameCapture>,
+    /// Where captures are rendered again when the surface can't be copied from. Kept between
+    /// captures until the next resize.
+    capture_copy: Option<OffscreenTarget>,
+    clock: Clock,
+    lights: LightsState,
+    scene: Scene,
+    node_models: Vec<ModelState>,
+    node_instances: Vec<InstanceBuffer>,
+    /// Draws nodes that have no instances of their own once, where they are.
+    identity_instance: InstanceBuffer,
+    text: TextRenderer,
+    debug_ui: Option<DebugUi>,
+}
+
+/// Nodes shown one at a time by `State::set_challenge`.
+struct ChallengeNodes {
+    model: NodeId,
+    default: NodeId,
+    first: NodeId,
+    second: NodeId,
+    third: NodeId,
+    fourth: NodeId,
+}
+
+impl ChallengeNodes {
+    /// The pentagon scenes share the `model` node's transform; the clip-space triangle and
+    /// the tilted challenge 4 pentagon are roots of their own.
+    fn new(
+        scene: &mut Scene,
+        default_texture: TextureHandle,
+        house_texture: TextureHandle,
+    ) -> Self {
+        let material = NodeMaterial::new(default_texture, MaterialShader::Standard);
+        let model = scene.add(None, Node::new("Model", Model::default()));
+        let default = scene.add(
+            Some(model),
+            Node::new("Default", Model::default())
+                .with_mesh(NodeMesh::Pentagon)
+                .with_material(material),
+        );
+        let second = scene.add(
+            Some(model),
+            Node::new("Challenge2", Model::default())
+                .with_mesh(NodeMesh::PentagonChallenge2)
+                .with_material(material)
+                .with_visible(false),
+        );
+        let third = scene.add(
+            Some(model),
+            Node::new("Challenge3", Model::default())
+                .with_mesh(NodeMesh::Pentagon)
+                .with_material(NodeMaterial::new(house_texture, MaterialShader::Standard))
+                .with_visible(false),
+        );
+        let first = scene.add(
+            None,
+            Node::new("Challenge1", Model::default())
+                .with_mesh(NodeMesh::ClipTriangle)
+                .with_visible(false),
+        );
+        let fourth = scene.add(
+            None,
+            Node::new(
+                "Challenge4",
+                Model {
+                    rotation: Quaternion::from_angle_x(Deg(45.0)),
+                    ..Model::default()
+                },
+            )
+            .with_mesh(NodeMesh::Pentagon)
+            .with_material(NodeMaterial::new(
+                default_texture,
+                MaterialShader::Challenge4,
+            ))
+            .with_visible(false),
+        );
+
+        Self {
+            model,
+            default,
+            first,
+            second,
+            third,
+            fourth,
+        }
+    }
+
+    #[inline]
+    fn all(&self) -> [NodeId; 5] {
+        [
+            self.default,
+            self.first,
+            self.second,
+            self.third,
+            self.fourth,
+        ]
+    }
+
+    #[inline]
+    fn node(&self, challenge: Challenge) -> NodeId {
+        match challenge.into() {
+            None => self.default,
+            Some(ChallengeEnum::First) => self.first,
+            Some(ChallengeEnum::Second) => self.second,
+            Some(ChallengeEnum::Third) => self.third,
+            Some(ChallengeEnum::Fourth) => self.fourth,
+        }
+    }
 }
 
 impl State {
-    pub async fn new(window: &Window) -> Result<Self, Error> {
+    pub async fn new(window: &Window, assets_dir: impl Into<PathBuf>) -> Result<Self, Error> {
         let size = window.inner_size();
 
         let instance = Instance::new(Backends::all());
@@ -72,25 +222,120 @@ impl State {
             .await
             .ok_or(Error::RequestAdapterError)?;
 
-        let (device, queue) = adapter
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        // Screenshots read the surface back as 8-bit RGBA or BGRA.
+        let formats = surface.get_supported_formats(&adapter);
+        let format = formats
+            .iter()
+            .copied()
+            .find(|format| {
+                matches!(
+                    format,
+                    TextureFormat::Bgra8UnormSrgb
+                        | TextureFormat::Bgra8Unorm
+                        | TextureFormat::Rgba8UnormSrgb
+                        | TextureFormat::Rgba8Unorm
+                )
+            })
+            .ok_or(Error::UnsupportedSurfaceFormat(formats))?;
+        // Captures copy the frame straight off the surface where the format allows it.
+        let usage = if adapter
+            .get_texture_format_features(format)
+            .allowed_usages
+            .contains(TextureUsages::COPY_SRC)
+        {
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+        } else {
+            TextureUsages::RENDER_ATTACHMENT
+        };
+        let config = SurfaceConfiguration {
+            usage,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: PresentMode::AutoVsync,
+        };
+        surface.configure(&device, &config);
+
+        let result = Self::with_target(
+            adapter,
+            device,
+            queue,
+            config,
+            SampleCount::X4,
+            RenderTarget::Surface(surface),
+            AssetManager::new(assets_dir),
+        )?;
+
+        result.set_cursor_to_center(window)?;
+
+        Ok(result)
+    }
+
+    pub async fn new_headless(
+        size: PhysicalSize<u32>,
+        force_fallback_adapter: bool,
+        assets_dir: impl Into<PathBuf>,
+    ) -> Result<Self, Error> {
+        let instance = Instance::new(Backends::all());
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or(Error::RequestAdapterError)?;
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format: OffscreenTarget::FORMAT,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: PresentMode::AutoVsync,
+        };
+        let offscreen = OffscreenTarget::new(&device, &config);
+
+        Self::with_target(
+            adapter,
+            device,
+            queue,
+            config,
+            SampleCount::X1,
+            RenderTarget::Offscreen(offscreen),
+            AssetManager::new(assets_dir),
+        )
+    }
+
+    async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), Error> {
+        Ok(adapter
             .request_device(
                 &DeviceDescriptor {
-                    features: Features::empty(),
+                    features: adapter.features()
+                        & Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     limits: Limits::default(),
                     label: None,
                 },
                 None,
             )
-            .await?;
+            .await?)
+    }
 
-        let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
-            width: size.width.max(1),
-            height: size.height.max(1),
-            present_mode: PresentMode::AutoVsync,
-        };
-        surface.configure(&device, &config);
+    fn with_target(
+        adapter: Adapter,
+        device: Device,
+        queue: Queue,
+        config: SurfaceConfiguration,
+        sample_count: SampleCount,
+        target: RenderTarget,
+        mut assets: AssetManager,
+    ) -> Result<Self, Error> {
+        let size = PhysicalSize::new(config.width, config.height);
+        let sample_count = sample_count.supported(&adapter, config.format);
+        let msaa_view = create_msaa_view(&device, &config, sample_count);
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -111,77 +356,80 @@ impl State {
                         ty: BindingType::Sampler(SamplerBindingType::Filtering),
                         count: None,
                     },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("Texture binding group layout"),
             });
 
-        let diffuse_bytes = include_bytes!("../resources/happy-tree.png");
-        let diffuse_texture = TextureState::from_bytes(
-            &device,
-            &queue,
-            diffuse_bytes,
-            ImageFormat::Png,
-            "happy-tree.png texture",
-        )?;
-
-        let challenge3_bytes = include_bytes!("../resources/house.png");
-        let challenge3_texture = TextureState::from_bytes(
+        let diffuse_texture = assets.load_texture(
             &device,
             &queue,
-            challenge3_bytes,
-            ImageFormat::Png,
-            "house.png texture",
+            "happy-tree.png",
+            TextureOptions::mipmapped(),
         )?;
+        let house_texture =
+            assets.load_texture(&device, &queue, "house.png", TextureOptions::mipmapped())?;
 
-        let diffuse_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&diffuse_texture.view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("Diffuse bind group descriptor"),
-        });
+        let depth_texture =
+            TextureState::create_depth_texture(&device, &config, sample_count, "Depth texture");
 
-        let challenge3_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&challenge3_texture.view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&challenge3_texture.sampler),
-                },
-            ],
-            label: Some("Challenge3 bind group descriptor"),
-        });
-
-        let mat4x4_bind_group_layout =
+        let camera_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
-                label: Some("Mat4x4 bind group layout"),
+                ],
+                label: Some("Camera bind group layout"),
             });
 
-        let (render_pipeline, challenge_pipeline, challenge4_pipeline) = create_pipeline(
+        let lights = LightsState::new(&device, Lights::default());
+
+        let text = TextRenderer::new(&device)?;
+
+        let shaders = Shaders::load(&device, &mut assets)?;
+
+        let pipelines = create_pipeline(
             &device,
             &config,
-            &[&texture_bind_group_layout, &mat4x4_bind_group_layout],
+            sample_count,
+            &BindGroupLayouts {
+                scene: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &lights.bind_group_layout,
+                ],
+                shadow: &[&lights.shadow_bind_group_layout, &camera_bind_group_layout],
+                text: &[&text.bind_group_layout],
+            },
+            &shaders,
+            &assets,
         );
 
         let clear_color = Color {
@@ -218,6 +466,7 @@ impl State {
             target: (0.0, 0.0, 0.0).into(),
             up: Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
+            projection: Projection::Perspective,
             fovy: 45.0,
             znear: 0.1,
             zfar: 100.0,
@@ -232,47 +481,66 @@ impl State {
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
-        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: &mat4x4_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("Camera bind group"),
-        });
+        let mut scene = Scene::new();
+        let challenge_nodes = ChallengeNodes::new(&mut scene, diffuse_texture, house_texture);
+
+        let identity_instance = InstanceBuffer::new(&device, vec![MeshInstance::default()]);
 
         let camera_controller = CameraController::new(0.2);
 
         let challenge = Challenge::default();
 
-        let result = Self {
-            surface,
+        Ok(Self {
+            target,
+            adapter,
             device,
             queue,
             config,
             size,
             clear_color,
-            render_pipeline,
-            challenge_pipeline,
+            sample_count,
+            msaa_view,
+            texture_bind_group_layout,
+            camera_bind_group_layout,
+            shaders,
+            shader_watcher: None,
+            pipelines,
             challenge,
+            challenge_nodes,
             vertex_buffer,
             index_buffer,
             num_indices,
             index_buffer_challenge2,
             num_indices_challenge2,
-            diffuse_bind_group,
-            challenge3_bind_group,
-            _diffuse_texture: diffuse_texture,
+            assets,
+            default_texture: diffuse_texture,
+            material_bind_groups: HashMap::new(),
+            depth_texture,
             camera,
+            previous_camera: camera,
+            render_alpha: 1.0,
+            projection_transition: None,
             camera_uniform,
             camera_buffer,
-            camera_bind_group,
             camera_controller,
-        };
-
-        result.set_cursor_to_center(window)?;
-
-        Ok(result)
+            map_camera_controller: MapCameraController::new(MapCameraLimits::default()),
+            cursor_grabbed: false,
+            input_map: InputMap::default(),
+            quit_requested: false,
+            recorder: None,
+            screenshot_requested: false,
+            screenshot_dir: PathBuf::from("screenshots"),
+            frame_capture: None,
+            capture_copy: None,
+            clock: Clock::default(),
+            lights,
+            scene,
+            node_models: Vec::new(),
+            node_instances: Vec::new(),
+            identity_instance,
+            text,
+            debug_ui: None,
+        })
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -284,98 +552,983 @@ impl State {
         self.config.height = new_size.height;
         self.camera.aspect = new_size.width as f32 / new_size.height as f32;
         self.update_camera_uniform();
-        self.surface.configure(&self.device, &self.config);
+        self.target.configure(&self.device, &self.config);
+        self.capture_copy = None;
+        self.recreate_render_targets();
+    }
+
+    /// Switches multisampling, falling back to the closest count the adapter supports.
+    /// Returns the sample count actually in use.
+    pub fn set_sample_count(&mut self, sample_count: SampleCount) -> SampleCount {
+        let sample_count = sample_count.supported(&self.adapter, self.config.format);
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.recreate_render_targets();
+            self.rebuild_pipelines();
+        }
+        sample_count
+    }
+
+    #[inline]
+    pub fn get_sample_count(&self) -> SampleCount {
+        self.sample_count
+    }
+
+    fn recreate_render_targets(&mut self) {
+        self.msaa_view = create_msaa_view(&self.device, &self.config, self.sample_count);
+        self.depth_texture = TextureState::create_depth_texture(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            "Depth texture",
+        );
+    }
+
+    fn with_bind_group_layouts<R>(&self, f: impl FnOnce(&BindGroupLayouts) -> R) -> R {
+        f(&BindGroupLayouts {
+            scene: &[
+                &self.texture_bind_group_layout,
+                &self.camera_bind_group_layout,
+                &self.lights.bind_group_layout,
+            ],
+            shadow: &[
+                &self.lights.shadow_bind_group_layout,
+                &self.camera_bind_group_layout,
+            ],
+            text: &[&self.text.bind_group_layout],
+        })
+    }
+
+    fn create_pipelines(&self) -> Pipelines {
+        self.with_bind_group_layouts(|bind_group_layouts| {
+            create_pipeline(
+                &self.device,
+                &self.config,
+                self.sample_count,
+                bind_group_layouts,
+                &self.shaders,
+                &self.assets,
+            )
+        })
+    }
+
+    fn create_pipeline_of(&self, kind: PipelineKind) -> RenderPipeline {
+        self.with_bind_group_layouts(|bind_group_layouts| {
+            create_pipeline_of(
+                kind,
+                &self.device,
+                &self.config,
+                self.sample_count,
+                bind_group_layouts,
+                &self.shaders,
+                &self.assets,
+            )
+        })
+    }
+
+    fn rebuild_pipelines(&mut self) {
+        self.pipelines = self.create_pipelines();
+    }
+
+    /// Starts watching the assets directory for WGSL changes, picked up by `advance` every
+    /// `ShaderWatcher::POLL_INTERVAL` of real time.
+    pub fn enable_shader_hot_reload(&mut self) {
+        self.shader_watcher = Some(ShaderWatcher::new(self.assets.root()));
+    }
+
+    /// Shows the debug window over the scene; `Action::ToggleDebugUi` (F1) hides and shows it
+    /// again. Dear ImGui allows one context per process, so only one `State` can enable it at
+    /// a time.
+    pub fn enable_debug_ui(&mut self) {
+        if self.debug_ui.is_none() {
+            self.debug_ui = Some(DebugUi::new(&self.device, &self.queue, self.config.format));
+        }
+    }
+
+    /// Feeds `event` to the debug window as input from `window`, if the debug UI is enabled.
+    pub fn handle_debug_ui_event<T>(
+        &mut self,
+        window: &Window,
+        event: &Event<T>,
+    ) -> Result<(), Error> {
+        match &mut self.debug_ui {
+            Some(debug_ui) => debug_ui.handle_event(window, event),
+            None => Ok(()),
+        }
+    }
+
+    /// Dear ImGui's input state while the debug UI is enabled, for driving it without a window.
+    pub fn debug_ui_io_mut(&mut self) -> Option<&mut Io> {
+        self.debug_ui.as_mut().map(DebugUi::io_mut)
+    }
+
+    /// Recompiles changed pipeline shaders and rebuilds the pipelines built from them. A shader
+    /// that fails validation is logged and the last working one is kept. Returns whether
+    /// anything was rebuilt.
+    pub fn reload_changed_shaders(&mut self) -> bool {
+        let changed = match &mut self.shader_watcher {
+            Some(watcher) => watcher.poll(),
+            None => return false,
+        };
+
+        let mut previous = Vec::new();
+        for file_name in changed {
+            let handle = match self.assets.shader_handle(&file_name) {
+                Some(handle) => handle,
+                None => continue,
+            };
+            let shader = match self.assets.compile_shader(&self.device, &file_name) {
+                Ok(shader) => shader,
+                Err(err) => {
+                    log::error!("keeping the last working {}: {}", file_name, err.report());
+                    continue;
+                }
+            };
+            let replaced = self.assets.replace_shader(handle, shader);
+            previous.push((file_name, handle, replaced));
+        }
+        if previous.is_empty() {
+            return false;
+        }
+
+        let mut kinds = Vec::new();
+        for (_, handle, _) in &previous {
+            kinds.extend(self.shaders.pipelines_using(*handle));
+        }
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let pipelines = kinds
+            .into_iter()
+            .map(|kind| (kind, self.create_pipeline_of(kind)))
+            .collect::<Vec<_>>();
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            log::error!("keeping the last working pipelines: {}", err);
+            for (_, handle, shader) in previous {
+                self.assets.replace_shader(handle, shader);
+            }
+            return false;
+        }
+
+        for (kind, pipeline) in pipelines {
+            *self.pipelines.get_mut(kind) = pipeline;
+        }
+        log::info!(
+            "reloaded {}",
+            previous
+                .iter()
+                .map(|(file_name, _, _)| file_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        true
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
+        let action = self.input_map.map_event(event);
+        if let Some(debug_ui) = &self.debug_ui {
+            if debug_ui.wants_event(event) {
+                // Let go of held actions even if the debug window takes the release.
+                if let Some((action, ElementState::Released)) = action {
+                    self.record(event);
+                    self.handle_action(action, ElementState::Released);
+                }
+                return true;
+            }
+        }
+        self.record(event);
+        if let WindowEvent::Focused(false) = event {
+            self.set_camera_mode(CameraMode::Orbit);
+            return false;
+        }
+        match action {
+            Some((action, state)) => self.handle_action(action, state),
+            None if self.camera_mode() == CameraMode::Map => {
+                self.map_camera_controller.process_events(event)
+            }
+            None => {
+                self.map_camera_controller.track_cursor(event);
+                false
+            }
+        }
+    }
+
+    /// Handles a window event the way the app does: what `input` leaves closes the app,
+    /// resizes the frame or recolors the background. Replays go through here as well.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        if self.input(event) {
+            return;
+        }
         match event {
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(VirtualKeyCode::Space),
-                        ..
-                    },
-                ..
-            } => {
-                self.challenge = self.challenge.rotate();
+            WindowEvent::CloseRequested => self.quit_requested = true,
+            WindowEvent::Resized(size) => self.resize(*size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => self.resize(**new_inner_size),
+            WindowEvent::CursorMoved { position, .. } => self.update_color(position),
+            _ => {}
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ElementState) -> bool {
+        if state == ElementState::Released {
+            // Controllers let go of actions even after switching away from them.
+            let camera = self.camera_controller.process_action(action, state);
+            let map_camera = self.map_camera_controller.process_action(action, state);
+            return camera || map_camera;
+        }
+        match action {
+            Action::ToggleDebugUi => match &mut self.debug_ui {
+                Some(debug_ui) => {
+                    debug_ui.visible = !debug_ui.visible;
+                    true
+                }
+                None => false,
+            },
+            Action::ToggleMouseLook => {
+                self.set_camera_mode(self.camera_mode().toggle(CameraMode::MouseLook));
+                true
+            }
+            Action::ToggleProjection => {
+                self.toggle_projection();
+                true
+            }
+            Action::ToggleMapCamera => {
+                self.set_camera_mode(self.camera_mode().toggle(CameraMode::Map));
+                true
+            }
+            Action::TogglePause => {
+                self.clock.toggle_pause();
+                true
+            }
+            Action::NextChallenge => {
+                self.set_challenge(self.challenge.rotate());
                 true
             }
-            event => self.camera_controller.process_events(event),
+            Action::Screenshot => {
+                self.request_screenshot();
+                true
+            }
+            Action::ToggleFrameCapture => {
+                let result = if self.is_capturing_frames() {
+                    self.stop_frame_capture().map(|saved| {
+                        log::info!("captured {} frames", saved);
+                    })
+                } else {
+                    let dir = PathBuf::from("captures")
+                        .join(format!("capture-{}", timestamp(SystemTime::now())));
+                    FrameCapture::start(dir, 1)
+                        .and_then(|frame_capture| self.start_frame_capture(frame_capture))
+                };
+                if let Err(err) = result {
+                    log::error!("frame capture failed: {}", err.report());
+                }
+                true
+            }
+            Action::Quit => {
+                self.quit_requested = true;
+                true
+            }
+            Action::MapOrbit | Action::MapPan if self.camera_mode() == CameraMode::Map => {
+                self.map_camera_controller.process_action(action, state)
+            }
+            action => self.camera_controller.process_action(action, state),
         }
     }
 
+    /// Adds `event` to the recording, if one is running. Only what reaches the scene is
+    /// recorded, since replays run without the debug window.
+    fn record(&mut self, event: &WindowEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Some(event) = RecordedEvent::from_window_event(event) {
+                recorder.record(event);
+            }
+        }
+    }
+
+    /// Records the input from here on to `path`, to be played back with `Replay`.
+    pub fn start_recording(&mut self, path: impl Into<PathBuf>) -> Result<(), Error> {
+        self.recorder = Some(Recorder::create(path, &RecordingHeader::from_state(self))?);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Whether `Action::Quit` was triggered or the window was asked to close.
+    #[inline]
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    #[inline]
+    pub fn input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    /// Replaces the bindings, e.g. with ones loaded by `InputMap::load`.
+    #[inline]
+    pub fn set_input_map(&mut self, input_map: InputMap) {
+        self.input_map = input_map;
+    }
+
+    /// Feeds raw mouse motion to the camera controller while in mouse-look mode.
+    #[inline]
+    pub fn mouse_motion(&mut self, delta: (f64, f64)) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::MouseMotion(delta.0, delta.1));
+        }
+        self.camera_controller.process_mouse_motion(delta);
+    }
+
+    #[inline]
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Moves the camera to `camera` at once, without blending towards it.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.previous_camera = camera;
+        self.projection_transition = None;
+        self.update_camera_uniform();
+    }
+
+    #[inline]
+    pub fn projection(&self) -> Projection {
+        self.camera.projection
+    }
+
+    #[inline]
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.camera.projection = projection;
+        self.projection_transition = None;
+    }
+
+    /// Switches between perspective and orthographic projection without changing the size of
+    /// the view around the camera's target. The camera switches at once, and rendering follows
+    /// over `ProjectionTransition::DURATION` of simulated time. Does nothing with the eye on
+    /// the target.
+    pub fn toggle_projection(&mut self) {
+        if self.camera.toggle_projection() {
+            self.start_projection_transition();
+        }
+    }
+
+    fn start_projection_transition(&mut self) {
+        self.projection_transition = Some(ProjectionTransition::new(
+            &self.camera,
+            self.projection_transition.as_ref(),
+        ));
+    }
+
+    #[inline]
+    pub fn map_camera_limits_mut(&mut self) -> &mut MapCameraLimits {
+        self.map_camera_controller.limits_mut()
+    }
+
+    #[inline]
+    pub fn camera_mode(&self) -> CameraMode {
+        self.camera_controller.mode()
+    }
+
+    #[inline]
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.camera_controller.set_mode(mode);
+    }
+
+    /// Grabs and hides the cursor in mouse-look mode and releases it otherwise.
+    pub fn update_cursor(&mut self, window: &Window) -> Result<(), Error> {
+        let grab = self.camera_mode() == CameraMode::MouseLook;
+        if grab != self.cursor_grabbed {
+            // Only try once per mode change, even if grabbing isn't supported.
+            self.cursor_grabbed = grab;
+            window.set_cursor_visible(!grab);
+            window.set_cursor_grab(grab)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Pause, time scale and timestep of the simulation.
+    #[inline]
+    pub fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    /// Runs the simulation steps for the real time since the last tick and prepares rendering
+    /// between the last two of them.
+    pub fn tick(&mut self) -> FrameTime {
+        let real = self.clock.lap();
+        match &self.frame_capture {
+            // Captured frames are evenly spaced in simulated time however long they take.
+            Some(frame_capture) => self.advance(frame_capture.render_time()),
+            None => self.advance(real),
+        }
+    }
+
+    /// Like `tick`, but for `real` time instead of the time since the last tick.
+    pub fn advance(&mut self, real: Duration) -> FrameTime {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.finish_frame(real) {
+                log::error!("stopped recording input: {}", err.report());
+                self.recorder = None;
+            }
+        }
+        let time = self.clock.advance(real);
+        // Real rather than simulated time, so shaders reload while paused too.
+        if let Some(watcher) = &mut self.shader_watcher {
+            if watcher.is_due(real) {
+                self.reload_changed_shaders();
+            }
+        }
+        for _ in 0..time.steps {
+            self.update();
+        }
+        self.render_alpha = time.alpha;
+        self.update_camera_uniform();
+        time
+    }
+
+    /// Advances the simulation by one fixed timestep, regardless of the clock.
     pub fn update(&mut self) {
+        self.previous_camera = self.camera;
         self.camera_controller.update_camera(&mut self.camera);
+        if self.camera_mode() == CameraMode::Map {
+            self.map_camera_controller
+                .update_camera(&mut self.camera, self.size);
+        }
+        if let Some(transition) = &mut self.projection_transition {
+            if !transition.update(self.clock.timestep()) {
+                self.projection_transition = None;
+            }
+        }
+        self.render_alpha = 1.0;
         self.update_camera_uniform();
     }
 
+    /// Transform shared by the default scene and challenges 2 and 3.
+    #[inline]
+    pub fn model_mut(&mut self) -> &mut Model {
+        &mut self.scene.node_mut(self.challenge_nodes.model).transform
+    }
+
+    #[inline]
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    #[inline]
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    #[inline]
+    pub fn assets(&self) -> &AssetManager {
+        &self.assets
+    }
+
+    pub fn load_texture(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        self.assets
+            .load_texture(&self.device, &self.queue, path, options)
+    }
+
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Result<MeshHandle, Error> {
+        self.assets.load_mesh(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            path,
+        )
+    }
+
+    /// Adds a node drawing `mesh` to the default scene.
+    pub fn show_mesh(&mut self, mesh: MeshHandle) -> NodeId {
+        self.scene.add(
+            Some(self.challenge_nodes.default),
+            Node::new("Mesh", Model::default()).with_mesh(NodeMesh::Asset(mesh)),
+        )
+    }
+
+    /// The node shown for `challenge`.
+    #[inline]
+    pub fn challenge_node(&self, challenge: Challenge) -> NodeId {
+        self.challenge_nodes.node(challenge)
+    }
+
+    /// Copies of node `id`'s mesh, placed relative to the node. A node without instances is
+    /// drawn once.
+    pub fn instances_mut(&mut self, id: NodeId) -> &mut InstanceBuffer {
+        self.grow_node_instances(id.index() + 1);
+        &mut self.node_instances[id.index()]
+    }
+
+    fn grow_node_instances(&mut self, len: usize) {
+        while self.node_instances.len() < len {
+            self.node_instances
+                .push(InstanceBuffer::new(&self.device, Vec::new()));
+        }
+    }
+
+    /// The instances node `id` is drawn with.
+    #[inline]
+    fn node_instances(&self, id: NodeId) -> &InstanceBuffer {
+        match self.node_instances.get(id.index()) {
+            Some(instances) if !instances.instances().is_empty() => instances,
+            _ => &self.identity_instance,
+        }
+    }
+
+    /// The camera as rendered this frame, between the last two simulation steps.
+    #[inline]
+    fn render_camera(&self) -> Camera {
+        let mut camera = self
+            .previous_camera
+            .interpolate(&self.camera, self.render_alpha);
+        if let Some(transition) = &self.projection_transition {
+            transition.apply(&mut camera, self.render_alpha);
+        }
+        camera
+    }
+
     fn update_camera_uniform(&mut self) {
-        self.camera_uniform.update_view_proj(&self.camera);
+        self.camera_uniform.update_view_proj(&self.render_camera());
         self.queue
             .write_buffer(&self.camera_buffer, 0, cast_slice(&[self.camera_uniform]));
     }
 
+    #[inline]
+    pub fn lights_mut(&mut self) -> &mut Lights {
+        &mut self.lights.lights
+    }
+
+    /// Material of nodes that do not set one.
+    #[inline]
+    fn default_material(&self) -> NodeMaterial {
+        NodeMaterial::new(self.default_texture, MaterialShader::Standard)
+    }
+
+    /// Uploads the lights and the world matrix and instances of every node, and creates bind
+    /// groups for new node materials.
+    fn prepare_scene(&mut self) {
+        self.lights.update_uniform(&self.queue);
+        self.scene.update_world_matrices();
+
+        self.identity_instance.upload(&self.device, &self.queue);
+        self.grow_node_instances(self.scene.len());
+        for instances in &mut self.node_instances {
+            instances.upload(&self.device, &self.queue);
+        }
+
+        while self.node_models.len() < self.scene.len() {
+            self.node_models.push(ModelState::new(
+                &self.device,
+                &self.camera_bind_group_layout,
+                &self.camera_buffer,
+                "Scene node model",
+            ));
+        }
+        for ((_, node), model) in self.scene.iter().zip(&mut self.node_models) {
+            model.update_uniform(&self.queue, node.world_matrix());
+        }
+
+        let materials = iter::once(self.default_material())
+            .chain(self.scene.iter().filter_map(|(_, node)| node.material));
+        for material in materials {
+            let assets = &self.assets;
+            let device = &self.device;
+            let layout = &self.texture_bind_group_layout;
+            self.material_bind_groups
+                .entry((material.texture, material.phong))
+                .or_insert_with(|| {
+                    Material::create_bind_group(
+                        device,
+                        layout,
+                        assets.texture(material.texture),
+                        material.phong,
+                        "Node material bind group",
+                    )
+                });
+        }
+    }
+
+    /// Index buffer and index count of a built-in pentagon mesh.
+    #[inline]
+    fn pentagon_indices(&self, mesh: NodeMesh) -> (&Buffer, u32) {
+        if mesh == NodeMesh::PentagonChallenge2 {
+            (&self.index_buffer_challenge2, self.num_indices_challenge2)
+        } else {
+            (&self.index_buffer, self.num_indices)
+        }
+    }
+
+    #[inline]
+    fn material_pipeline(&self, shader: MaterialShader) -> &RenderPipeline {
+        match shader {
+            MaterialShader::Standard => &self.pipelines.render,
+            MaterialShader::Challenge4 => &self.pipelines.challenge4,
+        }
+    }
+
+    /// Applies the edits made in the debug window, given the challenge and camera from before
+    /// it was built.
+    fn apply_debug_ui_edits(&mut self, challenge: Challenge, camera: Camera) {
+        if self.challenge != challenge {
+            self.set_challenge(self.challenge);
+        }
+        if self.camera != camera {
+            // Show edits right away instead of blending towards them.
+            self.previous_camera = self.camera;
+        }
+        if mem::discriminant(&self.camera.projection) != mem::discriminant(&camera.projection) {
+            self.start_projection_transition();
+        }
+    }
+
+    /// Draws `section` over the scene in the next frame.
+    #[inline]
+    pub fn queue_text(&mut self, section: TextSection) {
+        self.text.queue(section);
+    }
+
+    /// Width and height in pixels `text` would take up at line height `size`.
+    #[inline]
+    pub fn measure_text(&self, text: &str, size: f32) -> (f32, f32) {
+        self.text.measure(text, size)
+    }
+
+    /// Renders a frame, saves it as a screenshot if one was requested and hands it to the frame
+    /// capture if one is running.
     pub fn render(&mut self) -> Result<(), Error> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&TextureViewDescriptor::default());
+        let screenshot = mem::take(&mut self.screenshot_requested);
+        let capture_frame = self
+            .frame_capture
+            .as_mut()
+            .is_some_and(FrameCapture::next_frame);
+        let image = match self.render_frame(screenshot || capture_frame)? {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        // A failed screenshot still leaves the frame to the capture.
+        let saved = if screenshot {
+            self.write_screenshot(&image).map(drop)
+        } else {
+            Ok(())
+        };
+        if let (true, Some(frame_capture)) = (capture_frame, &mut self.frame_capture) {
+            if let Err(err) = frame_capture.capture(image) {
+                self.frame_capture = None;
+                return Err(err);
+            }
+        }
+        saved
+    }
+
+    /// Renders a frame and returns a copy of it if, and only if, `capture` is set.
+    fn render_frame(&mut self, capture: bool) -> Result<Option<RgbaImage>, Error> {
+        // Picks up camera changes made since the last simulation step, like a projection switch.
+        self.update_camera_uniform();
+        self.prepare_scene();
+        self.text.prepare(
+            &self.device,
+            &self.queue,
+            self.config.width,
+            self.config.height,
+            self.render_camera().build_view_projection_matrix(),
+        );
+        // Without copying from the surface, captures render the frame again offscreen.
+        let copy_needed = capture
+            && matches!(self.target, RenderTarget::Surface(_))
+            && !self.config.usage.contains(TextureUsages::COPY_SRC);
+        if copy_needed && self.capture_copy.is_none() {
+            self.capture_copy = Some(OffscreenTarget::new(
+                &self.device,
+                &SurfaceConfiguration {
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                    ..self.config.clone()
+                },
+            ));
+        }
+        let frame = self.target.acquire()?;
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("Render encoder"),
             });
 
+        let visible_nodes = self.scene.visible_nodes();
+
         {
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Render pass"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(self.clear_color),
+            let mut shadow_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Shadow pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &self.lights.shadow_map.view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
                         store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
+                    }),
+                    stencil_ops: None,
+                }),
             });
+            shadow_pass.set_pipeline(&self.pipelines.shadow);
+            shadow_pass.set_bind_group(0, &self.lights.shadow_bind_group, &[]);
 
-            match self.challenge.into() {
-                None | Some(ChallengeEnum::Second | ChallengeEnum::Third) => {
-                    render_pass.set_pipeline(&self.render_pipeline);
-                    render_pass.set_bind_group(
-                        0,
-                        if let Some(ChallengeEnum::Third) = self.challenge.into() {
-                            &self.challenge3_bind_group
-                        } else {
-                            &self.diffuse_bind_group
-                        },
-                        &[],
-                    );
-                    render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            for &id in &visible_nodes {
+                let model_bind_group = &self.node_models[id.index()].bind_group;
+                let instances = self.node_instances(id);
+                shadow_pass.set_vertex_buffer(1, instances.slice());
+                match self.scene.node(id).mesh {
+                    None | Some(NodeMesh::ClipTriangle) => {}
+                    Some(mesh @ (NodeMesh::Pentagon | NodeMesh::PentagonChallenge2)) => {
+                        let (index_buffer, num_indices) = self.pentagon_indices(mesh);
+                        shadow_pass.set_bind_group(1, model_bind_group, &[]);
+                        shadow_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                        shadow_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+                        shadow_pass.draw_indexed(0..num_indices, 0, instances.range());
+                    }
+                    Some(NodeMesh::Asset(handle)) => {
+                        shadow_pass.set_bind_group(1, model_bind_group, &[]);
+                        for mesh in &self.assets.mesh(handle).meshes {
+                            shadow_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                            shadow_pass
+                                .set_index_buffer(mesh.index_buffer.slice(..), Mesh::INDEX_FORMAT);
+                            shadow_pass.draw_indexed(0..mesh.num_indices, 0, instances.range());
+                        }
+                    }
+                }
+            }
+        }
+
+        self.encode_main_pass(&mut encoder, frame.view(), &visible_nodes);
+        let copy = self.capture_copy.as_ref().filter(|_| copy_needed);
+        if let Some(copy) = copy {
+            self.encode_main_pass(&mut encoder, copy.view(), &visible_nodes);
+        }
+
+        // The debug window goes over every copy of the frame.
+        let (challenge, camera) = (self.challenge, self.camera);
+        if let Some(debug_ui) = &mut self.debug_ui {
+            let views = match &copy {
+                Some(copy) => vec![frame.view(), copy.view()],
+                None => vec![frame.view()],
+            };
+            debug_ui.render(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &views,
+                &self.config,
+                DebugUiTargets {
+                    camera: &mut self.camera,
+                    camera_controller: &mut self.camera_controller,
+                    challenge: &mut self.challenge,
+                    clear_color: &mut self.clear_color,
+                    clock: &mut self.clock,
+                    input_map: &self.input_map,
+                },
+            );
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+        let image = match (capture, copy) {
+            (false, _) => Ok(None),
+            (true, Some(copy)) => copy
+                .read_image(&self.device, &self.queue, &self.config)
+                .map(Some),
+            (true, None) => frame
+                .read_image(&self.device, &self.queue, &self.config)
+                .map(Some),
+        };
+        frame.present();
+        if self.debug_ui.is_some() {
+            self.apply_debug_ui_edits(challenge, camera);
+        }
+        image
+    }
+
+    /// Draws the scene and text into `view`, resolving multisampling into it. The debug window
+    /// is drawn over it afterwards by `render_frame`.
+    fn encode_main_pass(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        visible_nodes: &[NodeId],
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Render pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: self.msaa_view.as_ref().unwrap_or(view),
+                resolve_target: self.msaa_view.as_ref().map(|_| view),
+                ops: Operations {
+                    load: LoadOp::Clear(self.clear_color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        for &id in visible_nodes {
+            let node = self.scene.node(id);
+            let mesh = match node.mesh {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let material = node.material.unwrap_or_else(|| self.default_material());
+            let material_bind_group =
+                &self.material_bind_groups[&(material.texture, material.phong)];
+            let instances = self.node_instances(id);
+
+            match mesh {
+                NodeMesh::ClipTriangle => {
+                    render_pass.set_pipeline(&self.pipelines.challenge);
+                    render_pass.draw(0..3, 0..1);
+                }
+                NodeMesh::Pentagon | NodeMesh::PentagonChallenge2 => {
+                    let (index_buffer, num_indices) = self.pentagon_indices(mesh);
+                    render_pass.set_pipeline(self.material_pipeline(material.shader));
+                    render_pass.set_bind_group(0, material_bind_group, &[]);
+                    render_pass.set_bind_group(1, &self.node_models[id.index()].bind_group, &[]);
+                    render_pass.set_bind_group(2, &self.lights.bind_group, &[]);
                     render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-                    let (index_buffer, num_indices) =
-                        if let Some(ChallengeEnum::Second) = self.challenge.into() {
-                            (&self.index_buffer_challenge2, self.num_indices_challenge2)
-                        } else {
-                            (&self.index_buffer, self.num_indices)
-                        };
+                    render_pass.set_vertex_buffer(1, instances.slice());
                     render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
-                    render_pass.draw_indexed(0..num_indices, 0, 0..1);
+                    render_pass.draw_indexed(0..num_indices, 0, instances.range());
                 }
-                Some(ChallengeEnum::First) => {
-                    render_pass.set_pipeline(&self.challenge_pipeline);
-                    render_pass.draw(0..3, 0..1);
+                NodeMesh::Asset(handle) => {
+                    let asset = self.assets.mesh(handle);
+                    render_pass.set_pipeline(self.material_pipeline(material.shader));
+                    render_pass.set_bind_group(1, &self.node_models[id.index()].bind_group, &[]);
+                    render_pass.set_bind_group(2, &self.lights.bind_group, &[]);
+                    render_pass.set_vertex_buffer(1, instances.slice());
+                    for mesh in &asset.meshes {
+                        let bind_group = match node.material {
+                            Some(_) => material_bind_group,
+                            None => &asset.materials[mesh.material].bind_group,
+                        };
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                        render_pass
+                            .set_index_buffer(mesh.index_buffer.slice(..), Mesh::INDEX_FORMAT);
+                        render_pass.draw_indexed(0..mesh.num_indices, 0, instances.range());
+                    }
                 }
             }
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        render_pass.set_pipeline(&self.pipelines.text);
+        self.text.draw(&mut render_pass);
+    }
+
+    /// Renders a frame and returns it as an RGBA image.
+    pub fn render_to_image(&mut self) -> Result<RgbaImage, Error> {
+        Ok(self
+            .render_frame(true)?
+            .expect("captured frames are returned"))
+    }
+
+    pub fn save_png(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        self.render_to_image()?
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(|err| Error::ImageSaveError(path.to_owned(), err))
+    }
+
+    /// Hands rendered frames to `frame_capture` until `stop_frame_capture`, and makes `tick`
+    /// advance by its render time per frame. Replaces a running capture.
+    pub fn start_frame_capture(&mut self, frame_capture: FrameCapture) -> Result<(), Error> {
+        self.stop_frame_capture()?;
+        self.frame_capture = Some(frame_capture);
         Ok(())
     }
 
+    /// Waits for the captured frames to be saved and returns how many were.
+    pub fn stop_frame_capture(&mut self) -> Result<u64, Error> {
+        match self.frame_capture.take() {
+            Some(frame_capture) => frame_capture.finish(),
+            None => Ok(0),
+        }
+    }
+
+    #[inline]
+    pub fn is_capturing_frames(&self) -> bool {
+        self.frame_capture.is_some()
+    }
+
+    /// Saves the next frame `render` draws to the screenshot directory.
+    #[inline]
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Directory screenshots are saved to, `screenshots` by default. It is created as needed.
+    #[inline]
+    pub fn set_screenshot_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.screenshot_dir = dir.into();
+    }
+
+    /// Renders a frame and saves it to the screenshot directory, returning the file's path.
+    pub fn save_screenshot(&mut self) -> Result<PathBuf, Error> {
+        let image = self.render_to_image()?;
+        self.write_screenshot(&image)
+    }
+
+    fn write_screenshot(&self, image: &RgbaImage) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.screenshot_dir)
+            .map_err(|err| Error::ScreenshotIoError(self.screenshot_dir.clone(), err))?;
+        let (path, file) = self.create_screenshot_file(&timestamp(SystemTime::now()))?;
+        image
+            .write_to(&mut BufWriter::new(file), ImageOutputFormat::Png)
+            .map_err(|err| Error::ImageSaveError(path.clone(), err))?;
+        log::info!("saved screenshot {}", path.display());
+        Ok(path)
+    }
+
+    /// Creates `screenshot-<stamp>.png`, numbering it `screenshot-<stamp>-1.png` and so on if
+    /// screenshots taken within the same millisecond already have the name.
+    fn create_screenshot_file(&self, stamp: &str) -> Result<(PathBuf, File), Error> {
+        for n in 0u32.. {
+            let name = match n {
+                0 => format!("screenshot-{}.png", stamp),
+                n => format!("screenshot-{}-{}.png", stamp, n),
+            };
+            let path = self.screenshot_dir.join(name);
+            match File::options().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(Error::ScreenshotIoError(path, err)),
+            }
+        }
+        unreachable!("ran out of screenshot names")
+    }
+
+    #[inline]
+    pub fn challenge(&self) -> Challenge {
+        self.challenge
+    }
+
+    #[inline]
+    pub fn set_challenge(&mut self, challenge: Challenge) {
+        self.challenge = challenge;
+        let shown = self.challenge_nodes.node(challenge);
+        for id in self.challenge_nodes.all() {
+            self.scene.node_mut(id).visible = id == shown;
+        }
+    }
+
     pub fn update_color(&mut self, position: &PhysicalPosition<f64>) {
         self.clear_color.r = position.x / self.size.width as f64;
         self.clear_color.b = position.y / self.size.height as f64;
@@ -397,3 +1550,34 @@ impl State {
         Ok(())
     }
 }
+
+/// UTC date and time for file names, e.g. `2024-05-01_13-45-07.250`.
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Days since 1970-01-01 to a proleptic Gregorian date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
@@ -0,0 +1,603 @@
+use crate::err::Error;
+use ab_glyph::{point, Font, FontArc, GlyphId, InvalidFont, Point, PxScale, ScaleFont};
+use bytemuck::{cast_slice, Pod, Zeroable};
+use cgmath::{Matrix4, Point3, Vector4};
+use std::{collections::HashMap, mem, num::NonZeroU32};
+use ttf_parser::{
+    gpos::{PairAdjustment, PositioningSubtable},
+    Face, Tag,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+    Buffer, BufferAddress, BufferBindingType, BufferDescriptor, BufferUsages, Device, Extent3d,
+    FilterMode, ImageCopyTexture, ImageDataLayout, IndexFormat, Origin3d, Queue, RenderPass,
+    SamplerBindingType, SamplerDescriptor, ShaderStages, Texture, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+
+/// Where a `TextSection` is placed on screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextAnchor {
+    /// Top-left corner of the text, in pixels from the top-left of the window.
+    Screen(f32, f32),
+    /// World position the text is centered above, e.g. a town name. Text stays the same size
+    /// on screen and is hidden when the position is behind the camera.
+    World(Point3<f32>),
+}
+
+/// A string drawn for a single frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSection {
+    pub text: String,
+    pub anchor: TextAnchor,
+    /// Line height in pixels.
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl TextSection {
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    #[inline]
+    pub fn new(text: impl Into<String>, anchor: TextAnchor, size: f32) -> Self {
+        Self {
+            text: text.into(),
+            anchor,
+            size,
+            color: Self::WHITE,
+        }
+    }
+
+    #[inline]
+    pub fn with_color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct TextVertex {
+    /// Pixels from the top-left of the window.
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBS: [VertexAttribute; 3] =
+        vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    #[inline]
+    pub const fn desc<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+const FONT_DATA: &[u8] = include_bytes!("../resources/Roboto-Regular.ttf");
+
+/// Pair adjustments of the font's GPOS `kern` feature. `ab_glyph` only reads the legacy `kern`
+/// table, which Roboto doesn't have.
+struct Kerning {
+    pairs: Vec<PairAdjustment<'static>>,
+}
+
+impl Kerning {
+    fn new(face: &Face<'static>) -> Self {
+        let gpos = match face.tables().gpos {
+            Some(gpos) => gpos,
+            None => return Self { pairs: Vec::new() },
+        };
+        let mut lookups = gpos
+            .features
+            .into_iter()
+            .filter(|feature| feature.tag == Tag::from_bytes(b"kern"))
+            .flat_map(|feature| feature.lookup_indices)
+            .collect::<Vec<_>>();
+        lookups.sort_unstable();
+        lookups.dedup();
+
+        let pairs = lookups
+            .into_iter()
+            .filter_map(|index| gpos.lookups.get(index))
+            .flat_map(|lookup| lookup.subtables.into_iter::<PositioningSubtable>())
+            .filter_map(|subtable| match subtable {
+                PositioningSubtable::Pair(pair) => Some(pair),
+                _ => None,
+            })
+            .collect();
+        Self { pairs }
+    }
+
+    /// Advance adjustment between `first` and `second` in font units, from the first
+    /// subtable that covers the pair.
+    fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> Option<f32> {
+        let (first, second) = (ttf_parser::GlyphId(first.0), ttf_parser::GlyphId(second.0));
+        self.pairs.iter().find_map(|pair| {
+            let index = pair.coverage().get(first)?;
+            let (record, _) = match pair {
+                PairAdjustment::Format1 { sets, .. } => sets.get(index)?.get(second)?,
+                PairAdjustment::Format2 {
+                    classes, matrix, ..
+                } => matrix.get((classes.0.get(first), classes.1.get(second)))?,
+            };
+            Some(f32::from(record.x_advance))
+        })
+    }
+}
+
+/// Location of a rasterized glyph in the atlas.
+#[derive(Copy, Clone, Debug)]
+struct AtlasGlyph {
+    /// Offset of the bitmap's top-left corner from the glyph origin on the baseline.
+    offset: [f32; 2],
+    size: [u32; 2],
+    origin: [u32; 2],
+}
+
+/// Single-channel texture that glyph bitmaps are packed into, row by row, as they are
+/// first drawn. When it fills up it is cleared and refilled with the glyphs still in use.
+struct GlyphAtlas {
+    texture: Texture,
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    cursor: [u32; 2],
+    row_height: u32,
+    /// Whether a glyph did not fit in the frame being prepared.
+    overflowed: bool,
+    /// Whether running out of room has been logged yet.
+    warned: bool,
+}
+
+impl GlyphAtlas {
+    const SIZE: u32 = 1024;
+    /// Empty texels around each glyph so linear filtering doesn't pick up its neighbours.
+    const PADDING: u32 = 1;
+
+    fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Glyph atlas"),
+            size: Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+
+        Self {
+            texture,
+            glyphs: HashMap::new(),
+            cursor: [Self::PADDING; 2],
+            row_height: 0,
+            overflowed: false,
+            warned: false,
+        }
+    }
+
+    /// Forgets every glyph and blanks the texture, so the padding of glyphs packed next
+    /// stays empty.
+    fn clear(&mut self, queue: &Queue) {
+        self.glyphs.clear();
+        self.cursor = [Self::PADDING; 2];
+        self.row_height = 0;
+        self.overflowed = false;
+        let size = [Self::SIZE; 2];
+        self.upload(queue, [0, 0], size, &vec![0; (size[0] * size[1]) as usize]);
+    }
+
+    /// Returns the glyph at `scale`, rasterizing and uploading it first if needed. Glyphs
+    /// without an outline, such as spaces, give `None`, and so do glyphs that no longer fit,
+    /// which are tried again after the next `clear`.
+    fn glyph(
+        &mut self,
+        queue: &Queue,
+        font: &FontArc,
+        id: GlyphId,
+        scale: PxScale,
+    ) -> Option<AtlasGlyph> {
+        let key = (id, scale.y.to_bits());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let outlined = match font.outline_glyph(id.with_scale(scale)) {
+            Some(outlined) => outlined,
+            None => {
+                self.glyphs.insert(key, None);
+                return None;
+            }
+        };
+        let bounds = outlined.px_bounds();
+        let size = [bounds.width() as u32, bounds.height() as u32];
+        let origin = self.allocate(size)?;
+
+        let mut pixels = vec![0u8; (size[0] * size[1]) as usize];
+        outlined.draw(|x, y, coverage| {
+            pixels[(y * size[0] + x) as usize] = (coverage * 255.0).round() as u8;
+        });
+        if size[0] > 0 && size[1] > 0 {
+            self.upload(queue, origin, size, &pixels);
+        }
+
+        let glyph = AtlasGlyph {
+            offset: [bounds.min.x, bounds.min.y],
+            size,
+            origin,
+        };
+        self.glyphs.insert(key, Some(glyph));
+        Some(glyph)
+    }
+
+    fn allocate(&mut self, [width, height]: [u32; 2]) -> Option<[u32; 2]> {
+        if self.cursor[0] + width + Self::PADDING > Self::SIZE {
+            self.cursor = [
+                Self::PADDING,
+                self.cursor[1] + self.row_height + Self::PADDING,
+            ];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + height + Self::PADDING > Self::SIZE {
+            if !self.warned {
+                log::warn!("glyph atlas is full, rebuilding it from the glyphs in use");
+                self.warned = true;
+            }
+            self.overflowed = true;
+            return None;
+        }
+
+        let origin = self.cursor;
+        self.cursor[0] += width + Self::PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(origin)
+    }
+
+    fn upload(&self, queue: &Queue, origin: [u32; 2], size: [u32; 2], pixels: &[u8]) {
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            pixels,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size[0]),
+                rows_per_image: NonZeroU32::new(size[1]),
+            },
+            Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Lays out `TextSection`s with the bundled Roboto font and draws them as textured quads over
+/// the scene.
+pub struct TextRenderer {
+    font: FontArc,
+    kerning: Kerning,
+    atlas: GlyphAtlas,
+    screen_buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    sections: Vec<TextSection>,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    /// Number of glyph quads the buffers have room for.
+    capacity: usize,
+    num_indices: u32,
+}
+
+impl TextRenderer {
+    pub fn new(device: &Device) -> Result<Self, Error> {
+        let font = FontArc::try_from_slice(FONT_DATA)?;
+        let kerning = Kerning::new(&Face::parse(FONT_DATA, 0).map_err(|_| InvalidFont)?);
+        let atlas = GlyphAtlas::new(device);
+
+        let screen_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Text screen buffer"),
+            contents: cast_slice(&[ScreenUniform::default()]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Text bind group layout"),
+        });
+
+        let view = atlas.texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Text bind group"),
+        });
+
+        let capacity = 64;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, capacity);
+
+        Ok(Self {
+            font,
+            kerning,
+            atlas,
+            screen_buffer,
+            bind_group_layout,
+            bind_group,
+            sections: Vec::new(),
+            vertex_buffer,
+            index_buffer,
+            capacity,
+            num_indices: 0,
+        })
+    }
+
+    /// Adds `section` to the text drawn by the next `prepare`.
+    #[inline]
+    pub fn queue(&mut self, section: TextSection) {
+        self.sections.push(section);
+    }
+
+    /// Width and height in pixels of `text` at line height `size`.
+    pub fn measure(&self, text: &str, size: f32) -> (f32, f32) {
+        let scaled = self.font.as_scaled(PxScale::from(size));
+        let width = text
+            .split('\n')
+            .filter_map(|line| {
+                let (id, position) = *self.layout(line, size).last()?;
+                Some(position.x + scaled.h_advance(id))
+            })
+            .fold(0.0, f32::max);
+        let lines = text.split('\n').count() as f32;
+        let height = scaled.height() * lines + scaled.line_gap() * (lines - 1.0);
+        (width, height)
+    }
+
+    /// Glyphs of `text` with their origins on the baseline, relative to the top-left corner
+    /// of the text. Adjacent glyphs are kerned.
+    fn layout(&self, text: &str, size: f32) -> Vec<(GlyphId, Point)> {
+        let scaled = self.font.as_scaled(PxScale::from(size));
+        let line_height = scaled.height() + scaled.line_gap();
+        let mut caret = point(0.0, scaled.ascent());
+        let mut previous = None;
+        let mut glyphs = Vec::new();
+
+        for c in text.chars() {
+            if c == '\n' {
+                caret = point(0.0, caret.y + line_height);
+                previous = None;
+                continue;
+            }
+            if c.is_control() {
+                continue;
+            }
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret.x += match self.kerning.kern_unscaled(previous, id) {
+                    Some(kern) => kern * scaled.h_scale_factor(),
+                    None => scaled.kern(previous, id),
+                };
+            }
+            glyphs.push((id, caret));
+            caret.x += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        glyphs
+    }
+
+    /// Lays out the queued sections for a `width` by `height` window, projecting world
+    /// anchors with `view_proj`, and uploads the glyph quads. Clears the queue.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        view_proj: Matrix4<f32>,
+    ) {
+        let sections = mem::take(&mut self.sections);
+        self.atlas.overflowed = false;
+        let mut vertices = self.vertices(queue, &sections, width, height, view_proj);
+        if self.atlas.overflowed {
+            // Evict the glyphs of earlier frames and lay out again with only this frame's.
+            self.atlas.clear(queue);
+            vertices = self.vertices(queue, &sections, width, height, view_proj);
+        }
+
+        let quads = vertices.len() / 4;
+        if quads > self.capacity {
+            self.capacity = quads.next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) = Self::create_buffers(device, self.capacity);
+        }
+        let indices = (0..quads as u32)
+            .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.vertex_buffer, 0, cast_slice(&vertices));
+        queue.write_buffer(&self.index_buffer, 0, cast_slice(&indices));
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            cast_slice(&[ScreenUniform {
+                size: [width as f32, height as f32],
+                ..Default::default()
+            }]),
+        );
+        self.num_indices = indices.len() as u32;
+    }
+
+    /// Glyph quads of `sections`, rasterizing glyphs into the atlas as needed. Glyphs that do
+    /// not fit are left out and mark the atlas as overflowed.
+    fn vertices(
+        &mut self,
+        queue: &Queue,
+        sections: &[TextSection],
+        width: u32,
+        height: u32,
+        view_proj: Matrix4<f32>,
+    ) -> Vec<TextVertex> {
+        let mut vertices = Vec::new();
+        for section in sections {
+            let origin = match section.anchor {
+                TextAnchor::Screen(x, y) => point(x, y),
+                TextAnchor::World(position) => {
+                    let clip = view_proj * Vector4::new(position.x, position.y, position.z, 1.0);
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    let (text_width, text_height) = self.measure(&section.text, section.size);
+                    point(
+                        (clip.x / clip.w + 1.0) * 0.5 * width as f32 - text_width * 0.5,
+                        (1.0 - clip.y / clip.w) * 0.5 * height as f32 - text_height,
+                    )
+                }
+            };
+            // Snap to whole pixels so glyph bitmaps map 1:1 onto the screen.
+            let origin = point(origin.x.round(), origin.y.round());
+            let scale = PxScale::from(section.size);
+
+            for (id, position) in self.layout(&section.text, section.size) {
+                let glyph = match self.atlas.glyph(queue, &self.font, id, scale) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let min = [
+                    origin.x + position.x.round() + glyph.offset[0],
+                    origin.y + position.y.round() + glyph.offset[1],
+                ];
+                let max = [min[0] + glyph.size[0] as f32, min[1] + glyph.size[1] as f32];
+                let atlas_size = GlyphAtlas::SIZE as f32;
+                let uv_min = [
+                    glyph.origin[0] as f32 / atlas_size,
+                    glyph.origin[1] as f32 / atlas_size,
+                ];
+                let uv_max = [
+                    (glyph.origin[0] + glyph.size[0]) as f32 / atlas_size,
+                    (glyph.origin[1] + glyph.size[1]) as f32 / atlas_size,
+                ];
+                let color = section.color;
+                vertices.extend([
+                    TextVertex {
+                        position: min,
+                        tex_coords: uv_min,
+                        color,
+                    },
+                    TextVertex {
+                        position: [min[0], max[1]],
+                        tex_coords: [uv_min[0], uv_max[1]],
+                        color,
+                    },
+                    TextVertex {
+                        position: max,
+                        tex_coords: uv_max,
+                        color,
+                    },
+                    TextVertex {
+                        position: [max[0], min[1]],
+                        tex_coords: [uv_max[0], uv_min[1]],
+                        color,
+                    },
+                ]);
+            }
+        }
+        vertices
+    }
+
+    /// Draws what the last `prepare` laid out; the pass must use the text pipeline.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+
+    fn create_buffers(device: &Device, capacity: usize) -> (Buffer, Buffer) {
+        let vertex_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Text vertex buffer"),
+            size: (capacity * 4 * mem::size_of::<TextVertex>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Text index buffer"),
+            size: (capacity * 6 * mem::size_of::<u32>()) as BufferAddress,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
+    }
+}
@@ -1,12 +1,60 @@
-use crate::err::Error;
+use crate::{
+    err::Error,
+    mipmap::{mip_level_count, MipmapGenerator},
+    msaa::SampleCount,
+};
 use image::{load_from_memory_with_format, DynamicImage, GenericImageView, ImageFormat};
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroU8};
 use wgpu::{
-    AddressMode, Device, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Queue,
-    Sampler, SamplerDescriptor, Texture, TextureAspect, TextureDescriptor, TextureDimension,
-    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+    AddressMode, CompareFunction, Device, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout,
+    Origin3d, Queue, Sampler, SamplerDescriptor, SurfaceConfiguration, Texture, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor,
 };
 
+/// How an image is stored on the GPU and sampled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TextureOptions {
+    /// `Rgba8UnormSrgb` for colors, `Rgba8Unorm` for data such as normal or height maps.
+    pub format: TextureFormat,
+    pub address_mode: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    /// Generates a full mip chain, filtered between levels with `min_filter`.
+    pub mipmaps: bool,
+    /// Maximum anisotropy, a power of two up to `MAX_ANISOTROPY`. Ignored by adapters without
+    /// anisotropic filtering; WebGPU also expects all filters to be `Linear`.
+    pub anisotropy_clamp: Option<NonZeroU8>,
+}
+
+impl Default for TextureOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::Rgba8UnormSrgb,
+            address_mode: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Nearest,
+            mipmaps: false,
+            anisotropy_clamp: None,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub const MAX_ANISOTROPY: u8 = 16;
+
+    /// Trilinear-filtered color texture with a mip chain.
+    #[inline]
+    pub fn mipmapped() -> Self {
+        Self {
+            min_filter: FilterMode::Linear,
+            mipmaps: true,
+            ..Default::default()
+        }
+    }
+}
+
 pub struct TextureState {
     pub texture: Texture,
     pub view: TextureView,
@@ -14,23 +62,122 @@ pub struct TextureState {
 }
 
 impl TextureState {
+    pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+    pub fn create_depth_texture(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        sample_count: SampleCount,
+        label: &str,
+    ) -> Self {
+        let size = Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: sample_count.count(),
+            dimension: TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            // Multisampled depth can't go through a filtering sampler anyway, and leaving it
+            // as a pure attachment lets GL backends allocate it as a renderbuffer.
+            usage: if sample_count == SampleCount::X1 {
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING
+            } else {
+                TextureUsages::RENDER_ATTACHMENT
+            },
+        });
+
+        Self::from_depth_texture(device, texture)
+    }
+
+    /// Square depth texture rendered from a light and sampled with comparisons.
+    pub fn create_shadow_map(device: &Device, size: u32, label: &str) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+
+        Self::from_depth_texture(device, texture)
+    }
+
+    fn from_depth_texture(device: &Device, texture: Texture) -> Self {
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            compare: Some(CompareFunction::LessEqual),
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
     pub fn from_bytes(
         device: &Device,
         queue: &Queue,
+        mipmaps: &mut MipmapGenerator,
         bytes: &[u8],
         format: ImageFormat,
         label: &str,
+        options: TextureOptions,
     ) -> Result<Self, Error> {
-        let img = load_from_memory_with_format(bytes, format)?;
-        Self::from_image(device, queue, &img, Some(label))
+        let img = load_from_memory_with_format(bytes, format)
+            .map_err(|err| Error::ImageImageError(label.to_owned(), err))?;
+        Self::from_image(device, queue, mipmaps, &img, Some(label), options)
     }
 
     pub fn from_image(
         device: &Device,
         queue: &Queue,
+        mipmaps: &mut MipmapGenerator,
         img: &DynamicImage,
         label: Option<&str>,
+        options: TextureOptions,
     ) -> Result<Self, Error> {
+        let format = options.format;
+        if !matches!(
+            format,
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+        ) {
+            return Err(Error::UnsupportedTextureFormat(
+                label.map(str::to_owned),
+                format,
+            ));
+        }
+
+        if let Some(clamp) = options.anisotropy_clamp {
+            let clamp = clamp.get();
+            if clamp > TextureOptions::MAX_ANISOTROPY || !clamp.is_power_of_two() {
+                return Err(Error::InvalidAnisotropyClamp(
+                    label.map(str::to_owned),
+                    clamp,
+                ));
+            }
+        }
+
         let rgba = img.to_rgba8();
         let (dimension_x, dimension_y) = img.dimensions();
 
@@ -39,14 +186,25 @@ impl TextureState {
             height: dimension_y,
             depth_or_array_layers: 1,
         };
+        let (mip_level_count, usage) = if options.mipmaps {
+            (
+                mip_level_count(dimension_x, dimension_y),
+                TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (1, TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST)
+        };
         let texture = device.create_texture(&TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
-            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            format,
+            usage,
         });
 
         queue.write_texture(
@@ -65,14 +223,21 @@ impl TextureState {
             size,
         );
 
+        mipmaps.generate(device, queue, &texture, size, format, mip_level_count);
+
         let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Nearest,
-            mipmap_filter: FilterMode::Nearest,
+            address_mode_u: options.address_mode,
+            address_mode_v: options.address_mode,
+            address_mode_w: options.address_mode,
+            mag_filter: options.mag_filter,
+            min_filter: options.min_filter,
+            mipmap_filter: if options.mipmaps {
+                options.min_filter
+            } else {
+                FilterMode::Nearest
+            },
+            anisotropy_clamp: options.anisotropy_clamp,
             ..Default::default()
         });
 
@@ -7,10 +7,36 @@ use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout
 pub struct Vertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
+    normal: [f32; 3],
 }
 
 impl Vertex {
-    const ATTRIBS: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+    #[inline]
+    pub const fn new(position: [f32; 3], tex_coords: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position,
+            tex_coords,
+            normal,
+        }
+    }
+
+    #[inline]
+    pub const fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    #[inline]
+    pub fn set_tex_coords(&mut self, tex_coords: [f32; 2]) {
+        self.tex_coords = tex_coords;
+    }
+
+    #[inline]
+    pub fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
+    }
+
+    const ATTRIBS: [VertexAttribute; 3] =
+        vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3];
 
     #[inline]
     pub const fn desc<'a>() -> VertexBufferLayout<'a> {
@@ -26,30 +52,37 @@ pub const VERTICES: &[Vertex] = &[
     Vertex {
         position: [-0.0868241, 0.49240386, 0.0],
         tex_coords: [0.4131759, 1.0 - 0.99240386],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [-0.49513406, 0.06958647, 0.0],
         tex_coords: [0.0048659444, 1.0 - 0.56958647],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [-0.21918549, -0.44939706, 0.0],
         tex_coords: [0.28081453, 1.0 - 0.05060294],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [0.35966998, -0.3473291, 0.0],
         tex_coords: [0.85967, 1.0 - 0.1526709],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [0.44147372, 0.2347359, 0.0],
         tex_coords: [0.9414737, 1.0 - 0.7347359],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [0.17732481, 0.46356988, 0.0],
         tex_coords: [0.6773248, 1.0 - 0.86356988],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         position: [0.10024225, -0.42836308, 0.0],
         tex_coords: [0.5702423, 1.0 - 0.10163692],
+        normal: [0.0, 0.0, 1.0],
     },
 ];
 
@@ -0,0 +1,65 @@
+mod common;
+
+use std::num::NonZeroU8;
+use town_links::{err::Error, texture::TextureOptions};
+use wgpu::{FilterMode, TextureFormat};
+use winit::dpi::PhysicalSize;
+
+#[test]
+fn textures_are_cached_and_missing_files_are_reported() {
+    let mut state = match common::headless_state(PhysicalSize::new(16, 16), common::RESOURCES) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let first = state
+        .load_texture("house.png", TextureOptions::default())
+        .expect("load house.png");
+    let second = state
+        .load_texture("house.png", TextureOptions::default())
+        .expect("load cached house.png");
+    assert_eq!(first, second);
+
+    match state.load_texture("no-such-texture.png", TextureOptions::default()) {
+        Err(Error::AssetNotFound(path)) => assert!(path.ends_with("no-such-texture.png")),
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok(handle) => panic!("unexpected texture {:?}", handle),
+    }
+}
+
+#[test]
+fn invalid_texture_options_are_rejected() {
+    let mut state = match common::headless_state(PhysicalSize::new(16, 16), common::RESOURCES) {
+        Some(state) => state,
+        None => return,
+    };
+
+    let bgra = TextureOptions {
+        format: TextureFormat::Bgra8UnormSrgb,
+        ..TextureOptions::default()
+    };
+    match state.load_texture("house.png", bgra) {
+        Err(Error::UnsupportedTextureFormat(label, format)) => {
+            assert_eq!(label.as_deref(), Some("house.png"));
+            assert_eq!(format, TextureFormat::Bgra8UnormSrgb);
+        }
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok(handle) => panic!("unexpected texture {:?}", handle),
+    }
+
+    let anisotropic = |clamp| TextureOptions {
+        min_filter: FilterMode::Linear,
+        anisotropy_clamp: NonZeroU8::new(clamp),
+        ..TextureOptions::mipmapped()
+    };
+    for clamp in [3, 32] {
+        match state.load_texture("house.png", anisotropic(clamp)) {
+            Err(Error::InvalidAnisotropyClamp(_, actual)) => assert_eq!(actual, clamp),
+            Err(err) => panic!("unexpected error {:?}", err),
+            Ok(handle) => panic!("unexpected texture {:?} for clamp {}", handle, clamp),
+        }
+    }
+    state
+        .load_texture("house.png", anisotropic(16))
+        .expect("anisotropy clamp of 16");
+}
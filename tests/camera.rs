@@ -0,0 +1,116 @@
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Vector4};
+use std::time::Duration;
+use town_links::{
+    camera::{Camera, Projection, ProjectionTransition},
+    map_camera_controller::{MapCameraController, MapCameraLimits},
+};
+use winit::dpi::PhysicalSize;
+
+const STEP: Duration = Duration::from_millis(10);
+
+fn camera() -> Camera {
+    Camera {
+        eye: Point3::new(0.0, 1.0, 2.0),
+        target: Point3::new(0.0, 0.0, 0.0),
+        up: Vector3::unit_y(),
+        aspect: 4.0 / 3.0,
+        projection: Projection::Perspective,
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 100.0,
+    }
+}
+
+/// Where `camera` puts a point next to its target on screen.
+fn project(camera: &Camera, point: Point3<f32>) -> (f32, f32) {
+    let clip: Vector4<f32> = camera.build_view_projection_matrix() * point.to_homogeneous();
+    (clip.x / clip.w, clip.y / clip.w)
+}
+
+/// `camera` as rendered after `steps` steps of the transition to its projection.
+fn rendered(camera: &Camera, transition: &mut ProjectionTransition, steps: u32) -> Camera {
+    for _ in 0..steps {
+        transition.update(STEP);
+    }
+    let mut rendered = *camera;
+    transition.apply(&mut rendered, 1.0);
+    rendered
+}
+
+#[test]
+fn switching_to_orthographic_dollies_out_while_narrowing_the_view() {
+    let (before, mut after) = (camera(), camera());
+    after.toggle_projection();
+    let mut transition = ProjectionTransition::new(&after, None);
+
+    // It starts out looking like the camera before the switch.
+    let start = rendered(&after, &mut transition, 0);
+    let difference: Matrix4<f32> =
+        start.build_view_projection_matrix() - before.build_view_projection_matrix();
+    assert!(difference.x.magnitude() + difference.y.magnitude() < 1e-4);
+    assert!(difference.z.magnitude() + difference.w.magnitude() < 1e-4);
+
+    // Halfway the eye has backed away with a narrower view, and a point at the target's depth
+    // stays where it was on screen.
+    let steps = (ProjectionTransition::DURATION.as_millis() / STEP.as_millis()) as u32;
+    let halfway = rendered(&after, &mut transition, steps / 2);
+    assert_eq!(halfway.projection, Projection::Perspective);
+    assert!(halfway.fovy < before.fovy);
+    assert!((halfway.eye - halfway.target).magnitude() > (before.eye - before.target).magnitude());
+    let point = Point3::new(0.5, 0.0, 0.0);
+    let (x, _) = project(&halfway, point);
+    assert!((x - project(&before, point).0).abs() < 1e-3);
+
+    // It ends at the orthographic camera, and stops one step later.
+    let end = rendered(&after, &mut transition, steps - steps / 2);
+    assert_eq!(end, after);
+    assert!(!transition.update(STEP));
+}
+
+#[test]
+fn switching_back_midway_reverses_from_where_it_is() {
+    let mut camera = camera();
+    camera.toggle_projection();
+    let mut to_orthographic = ProjectionTransition::new(&camera, None);
+    let steps = (ProjectionTransition::DURATION.as_millis() / STEP.as_millis()) as u32;
+    let orthographic = camera;
+    let midway = rendered(&orthographic, &mut to_orthographic, steps / 4);
+
+    camera.toggle_projection();
+    let mut to_perspective = ProjectionTransition::new(&camera, Some(&to_orthographic));
+    let reversed = rendered(&camera, &mut to_perspective, 0);
+    assert!((reversed.fovy - midway.fovy).abs() < 1e-3);
+    assert!((reversed.eye - midway.eye).magnitude() < 1e-3);
+}
+
+#[test]
+fn projection_stays_put_with_the_eye_on_the_target() {
+    let mut on_target = camera();
+    on_target.eye = on_target.target;
+    assert!(!on_target.toggle_projection());
+    assert_eq!(on_target.projection, Projection::Perspective);
+
+    // Moving the eye onto the target during a transition leaves the camera as it is.
+    let mut orthographic = camera();
+    assert!(orthographic.toggle_projection());
+    let mut transition = ProjectionTransition::new(&orthographic, None);
+    orthographic.eye = orthographic.target;
+    let rendered = rendered(&orthographic, &mut transition, 1);
+    assert_eq!(rendered, orthographic);
+    assert!(matches!(
+        rendered.projection,
+        Projection::Orthographic { height } if height.is_finite() && height > 0.0
+    ));
+}
+
+#[test]
+fn map_camera_on_its_target_moves_out_instead_of_breaking() {
+    let mut camera = camera();
+    camera.eye = camera.target;
+    let limits = MapCameraLimits::default();
+    MapCameraController::new(limits).update_camera(&mut camera, PhysicalSize::new(160, 120));
+
+    let distance = (camera.eye - camera.target).magnitude();
+    assert!((distance - limits.min_distance).abs() < 1e-5);
+    assert!(camera.build_view_projection_matrix().x.x.is_finite());
+}
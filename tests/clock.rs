@@ -0,0 +1,69 @@
+use std::time::Duration;
+use town_links::clock::{Clock, MAX_SCALE};
+
+const STEP: Duration = Duration::from_millis(10);
+
+#[test]
+fn splits_frames_into_fixed_steps() {
+    let mut clock = Clock::new(STEP);
+
+    let time = clock.advance(Duration::from_millis(25));
+    assert_eq!(time.steps, 2);
+    assert!((time.alpha - 0.5).abs() < 1e-4);
+
+    // The leftover half step carries over into the next frame.
+    let time = clock.advance(Duration::from_millis(5));
+    assert_eq!(time.steps, 1);
+    assert!(time.alpha.abs() < 1e-4);
+    assert_eq!(clock.elapsed(), STEP * 3);
+}
+
+#[test]
+fn simulates_the_same_time_at_any_frame_rate() {
+    let (mut slow, mut fast) = (Clock::new(STEP), Clock::new(STEP));
+    let slow_steps: u32 = (0..10)
+        .map(|_| slow.advance(Duration::from_millis(50)).steps)
+        .sum();
+    let fast_steps: u32 = (0..100)
+        .map(|_| fast.advance(Duration::from_millis(5)).steps)
+        .sum();
+    assert_eq!(slow_steps, 50);
+    assert_eq!(fast_steps, 50);
+}
+
+#[test]
+fn pausing_and_scaling_change_the_simulated_time() {
+    let mut clock = Clock::new(STEP);
+
+    clock.set_paused(true);
+    let time = clock.advance(Duration::from_millis(100));
+    assert_eq!((time.delta, time.steps), (Duration::ZERO, 0));
+
+    clock.set_paused(false);
+    clock.set_scale(0.5);
+    assert_eq!(clock.advance(Duration::from_millis(100)).steps, 5);
+
+    clock.set_scale(-1.0);
+    assert_eq!(clock.scale(), 0.0);
+}
+
+#[test]
+fn out_of_range_scales_are_clamped_or_ignored() {
+    let mut clock = Clock::new(STEP);
+    clock.set_scale(1e30);
+    assert_eq!(clock.scale(), MAX_SCALE);
+    // Even the longest frame at the fastest scale takes a bounded number of steps.
+    assert_eq!(clock.advance(Duration::from_secs(10)).steps, 400);
+
+    clock.set_scale(2.0);
+    for scale in [f32::INFINITY, f32::NEG_INFINITY, f32::NAN] {
+        clock.set_scale(scale);
+        assert_eq!(clock.scale(), 2.0);
+    }
+}
+
+#[test]
+fn long_stalls_are_not_caught_up_on() {
+    let mut clock = Clock::new(STEP);
+    assert_eq!(clock.advance(Duration::from_secs(10)).steps, 25);
+}
@@ -0,0 +1,24 @@
+use std::{env, path::Path};
+use town_links::{err::Error, state::State};
+use winit::dpi::PhysicalSize;
+
+/// The assets bundled with the crate.
+pub const RESOURCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
+
+/// A headless `State` loading assets from `assets`. Without a fallback adapter to render with
+/// this fails the test, unless `TOWN_LINKS_SKIP_GPU_TESTS` is set, in which case it returns
+/// `None` and the test should be skipped.
+pub fn headless_state(size: PhysicalSize<u32>, assets: impl AsRef<Path>) -> Option<State> {
+    let _ = env_logger::try_init();
+    match pollster::block_on(State::new_headless(size, true, assets.as_ref())) {
+        Ok(state) => Some(state),
+        Err(Error::RequestAdapterError) if env::var_os("TOWN_LINKS_SKIP_GPU_TESTS").is_some() => {
+            eprintln!("no fallback adapter available, skipping");
+            None
+        }
+        Err(err @ Error::RequestAdapterError) => {
+            panic!("{}; set TOWN_LINKS_SKIP_GPU_TESTS to skip GPU tests", err)
+        }
+        Err(err) => panic!("{}", err.report()),
+    }
+}
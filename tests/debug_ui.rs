@@ -0,0 +1,66 @@
+mod common;
+
+use std::{fs, path::PathBuf, time::Duration};
+use town_links::{
+    recording::{RecordedEvent, RecordedFrame},
+    state::State,
+};
+use winit::{
+    dpi::PhysicalSize,
+    event::{DeviceId, ElementState, ModifiersState, MouseButton, WindowEvent},
+};
+
+// Dear ImGui allows a single context per process, so this binary has only one test.
+#[test]
+fn clicks_on_the_debug_window_do_not_reach_the_scene_or_recordings() {
+    let mut state = match common::headless_state(PhysicalSize::new(320, 240), common::RESOURCES) {
+        Some(state) => state,
+        None => return,
+    };
+    state.enable_debug_ui();
+    #[allow(deprecated)]
+    let click = WindowEvent::MouseInput {
+        device_id: unsafe { DeviceId::dummy() },
+        state: ElementState::Pressed,
+        button: MouseButton::Left,
+        modifiers: ModifiersState::empty(),
+    };
+
+    // Without a window the cursor is placed through Dear ImGui's input state. The UI works out
+    // what is under the cursor while rendering, so each move is followed by a frame.
+    let move_to = |state: &mut State, x, y| {
+        state.debug_ui_io_mut().expect("debug UI enabled").mouse_pos = [x, y];
+        state.render_to_image().expect("headless render");
+    };
+
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("debug-ui-clicks.ron");
+    state.start_recording(&path).expect("start recording");
+
+    move_to(&mut state, 300.0, 230.0);
+    assert!(!state.input(&click), "click next to the debug window");
+    state.advance(Duration::from_millis(16));
+
+    move_to(&mut state, 20.0, 20.0);
+    assert!(state.input(&click), "click on the debug window");
+    state.advance(Duration::from_millis(16));
+    state.stop_recording();
+
+    let recording = fs::read_to_string(&path).expect("read recording");
+    let clicks = recording
+        .lines()
+        .skip(1)
+        .map(|line| ron::from_str::<RecordedFrame>(line).expect("parse frame"))
+        .map(|frame| {
+            frame
+                .events
+                .iter()
+                .filter(|event| matches!(event, RecordedEvent::MouseInput(..)))
+                .count()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        clicks,
+        [1, 0],
+        "only the click next to the window is recorded"
+    );
+}
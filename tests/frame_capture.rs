@@ -0,0 +1,46 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+use town_links::frame_capture::FrameCapture;
+use winit::dpi::PhysicalSize;
+
+#[test]
+fn captures_every_nth_frame_at_a_fixed_timestep() {
+    let mut state = match common::headless_state(PhysicalSize::new(160, 120), common::RESOURCES) {
+        Some(state) => state,
+        None => return,
+    };
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("frame-capture");
+    let _ = fs::remove_dir_all(&dir);
+
+    // Saved frames two steps apart, rendered every step.
+    let timestep = state.clock().timestep();
+    let frame_capture = FrameCapture::start(&dir, 2)
+        .expect("start frame capture")
+        .with_frame_time(timestep * 2);
+    assert_eq!(frame_capture.render_time(), timestep);
+    state
+        .start_frame_capture(frame_capture)
+        .expect("no capture running");
+    for _ in 0..5 {
+        let time = state.tick();
+        // Rendering takes however long it takes; each frame still simulates one step.
+        assert_eq!(time.delta, timestep);
+        assert_eq!(time.steps, 1);
+        state.render().expect("render");
+    }
+    assert_eq!(state.stop_frame_capture().expect("save frames"), 3);
+    assert!(!state.is_capturing_frames());
+
+    let mut saved = fs::read_dir(&dir)
+        .expect("capture directory")
+        .map(|entry| entry.expect("directory entry").file_name())
+        .collect::<Vec<_>>();
+    saved.sort();
+    assert_eq!(
+        saved,
+        ["frame-000000.png", "frame-000001.png", "frame-000002.png"]
+    );
+    let frame = image::open(dir.join("frame-000002.png")).expect("open frame");
+    assert_eq!((frame.width(), frame.height()), (160, 120));
+}
@@ -0,0 +1,366 @@
+mod common;
+
+use cgmath::{Angle, Deg, Point3, Quaternion, Rotation3, Vector3};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::{env, fs, path::PathBuf};
+use town_links::{
+    camera::{Projection, ProjectionTransition},
+    camera_controller::CameraMode,
+    challenge::{Challenge, ChallengeEnum},
+    instance::MeshInstance,
+    light::PointLight,
+    model::Model,
+    msaa::SampleCount,
+    state::State,
+    text::{TextAnchor, TextSection},
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
+        WindowEvent,
+    },
+};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_MISMATCHED_PIXELS: usize = 16;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn diff_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden-diff")
+}
+
+fn render(setup: impl FnOnce(&mut State)) -> Option<RgbaImage> {
+    let mut state = common::headless_state(PhysicalSize::new(WIDTH, HEIGHT), common::RESOURCES)?;
+    setup(&mut state);
+    Some(state.render_to_image().expect("headless render"))
+}
+
+fn pixel_matches(Rgba(actual): &Rgba<u8>, Rgba(expected): &Rgba<u8>) -> bool {
+    actual
+        .iter()
+        .zip(expected)
+        .all(|(a, e)| a.abs_diff(*e) <= CHANNEL_TOLERANCE)
+}
+
+fn check_scene(name: &str, challenge: Challenge, sample_count: SampleCount) {
+    check_scene_with(name, |state| {
+        state.set_challenge(challenge);
+        assert_eq!(
+            state.set_sample_count(sample_count),
+            sample_count,
+            "fallback adapter does not support {:?}",
+            sample_count
+        );
+    });
+}
+
+/// Renders the scene prepared by `setup` and compares it with `tests/golden/<name>.png`.
+/// Set `UPDATE_GOLDEN=1` to (re)write the reference image instead.
+fn check_scene_with(name: &str, setup: impl FnOnce(&mut State)) {
+    let actual = match render(setup) {
+        Some(image) => image,
+        None => return,
+    };
+    let golden_path = golden_dir().join(format!("{}.png", name));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        actual
+            .save_with_format(&golden_path, ImageFormat::Png)
+            .expect("write golden image");
+        return;
+    }
+
+    let expected = image::open(&golden_path)
+        .unwrap_or_else(|err| panic!("{}: {:?}", golden_path.display(), err))
+        .to_rgba8();
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{}: golden image size differs",
+        name
+    );
+
+    let mut diff = RgbaImage::new(WIDTH, HEIGHT);
+    let mut mismatched = 0;
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        if pixel_matches(actual_pixel, expected_pixel) {
+            let Rgba([r, g, b, _]) = *expected_pixel;
+            diff.put_pixel(x, y, Rgba([r / 4, g / 4, b / 4, 255]));
+        } else {
+            mismatched += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 255, 255]));
+        }
+    }
+
+    if mismatched > MAX_MISMATCHED_PIXELS {
+        let dir = diff_dir();
+        fs::create_dir_all(&dir).expect("create diff directory");
+        let actual_path = dir.join(format!("{}.actual.png", name));
+        let diff_path = dir.join(format!("{}.diff.png", name));
+        actual
+            .save_with_format(&actual_path, ImageFormat::Png)
+            .expect("write actual image");
+        diff.save_with_format(&diff_path, ImageFormat::Png)
+            .expect("write diff image");
+        panic!(
+            "{}: {} of {} pixels differ from {} (see {})",
+            name,
+            mismatched,
+            actual.width() * actual.height(),
+            golden_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn default_scene() {
+    check_scene("default", Challenge::default(), SampleCount::X1);
+}
+
+#[test]
+fn challenge_first() {
+    check_scene(
+        "challenge1",
+        Some(ChallengeEnum::First).into(),
+        SampleCount::X1,
+    );
+}
+
+#[test]
+fn challenge_second() {
+    check_scene(
+        "challenge2",
+        Some(ChallengeEnum::Second).into(),
+        SampleCount::X1,
+    );
+}
+
+#[test]
+fn challenge_third() {
+    check_scene(
+        "challenge3",
+        Some(ChallengeEnum::Third).into(),
+        SampleCount::X1,
+    );
+}
+
+#[test]
+fn challenge_fourth() {
+    check_scene(
+        "challenge4",
+        Some(ChallengeEnum::Fourth).into(),
+        SampleCount::X1,
+    );
+}
+
+#[test]
+fn default_scene_msaa4() {
+    check_scene("default_msaa4", Challenge::default(), SampleCount::X4);
+}
+
+#[test]
+fn instanced_markers() {
+    check_scene_with("instances", |state| {
+        let node = state.challenge_node(Challenge::default());
+        let instances = state.instances_mut(node);
+        for i in 0..5 {
+            let offset = i as f32 - 2.0;
+            instances.push(MeshInstance::new(
+                Model::new(
+                    Vector3::new(offset * 0.5, 0.0, -offset.abs() * 0.5),
+                    Quaternion::from_angle_z(Deg(offset * 20.0)),
+                    Vector3::new(0.4, 0.4, 0.4),
+                ),
+                [1.0, 0.25 * i as f32, 1.0 - 0.25 * i as f32, 1.0],
+            ));
+        }
+    });
+}
+
+#[test]
+fn instances_belong_to_their_node() {
+    check_scene_with("instances_on_ground", |state| {
+        // The ground is drawn once however many copies of the pentagon there are.
+        let ground = state
+            .load_mesh("../tests/fixtures/ground.obj")
+            .expect("load ground plane");
+        state.show_mesh(ground);
+        let node = state.challenge_node(Challenge::default());
+        let instances = state.instances_mut(node);
+        for i in 0..3 {
+            let offset = i as f32 - 1.0;
+            instances.push(MeshInstance::new(
+                Model::new(
+                    Vector3::new(offset * 0.6, 0.0, 0.0),
+                    Quaternion::from_angle_z(Deg(0.0)),
+                    Vector3::new(0.4, 0.4, 0.4),
+                ),
+                MeshInstance::WHITE,
+            ));
+        }
+    });
+}
+
+#[test]
+fn non_uniform_scale() {
+    check_scene_with("non_uniform_scale", |state| {
+        // Squashing the tilted pentagon along z turns it towards the camera. Its normal has to
+        // turn towards z with it, where the model matrix alone would turn it away.
+        state.model_mut().scale = Vector3::new(1.0, 1.0, 0.2);
+        let node = state.challenge_node(Challenge::default());
+        state.instances_mut(node).push(MeshInstance::new(
+            Model::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Quaternion::from_angle_x(Deg(-60.0)),
+                Vector3::new(1.0, 1.0, 1.0),
+            ),
+            MeshInstance::WHITE,
+        ));
+        state.lights_mut().directional.direction = Vector3::new(0.0, -1.0, -0.2);
+        state.update();
+    });
+}
+
+#[test]
+fn point_lights() {
+    check_scene_with("point_lights", |state| {
+        let lights = state.lights_mut();
+        lights.directional.color = [0.0, 0.0, 0.0];
+        lights.points.push(PointLight::new(
+            Vector3::new(-0.3, 0.3, 0.3),
+            [1.0, 0.2, 0.2],
+            1.0,
+        ));
+        lights.points.push(PointLight::new(
+            Vector3::new(0.3, -0.3, 0.3),
+            [0.2, 0.2, 1.0],
+            1.0,
+        ));
+    });
+}
+
+#[test]
+fn shadow_on_ground() {
+    check_scene_with("shadows", |state| {
+        let ground = state
+            .load_mesh("../tests/fixtures/ground.obj")
+            .expect("load ground plane");
+        state.show_mesh(ground);
+        // Throw the shadow towards the camera so the pentagon doesn't hide it.
+        state.lights_mut().directional.direction = Vector3::new(0.6, -1.0, 0.4);
+    });
+}
+
+#[test]
+fn gltf_node_transforms() {
+    check_scene_with("gltf_nodes", |state| {
+        // Two triangles under a scaled root, one of them turned on its side, above the pentagon.
+        let triangles = state
+            .load_mesh("../tests/fixtures/nodes.gltf")
+            .expect("load nodes.gltf");
+        state.show_mesh(triangles);
+    });
+}
+
+#[test]
+fn text_overlay() {
+    check_scene_with("text", |state| {
+        state.queue_text(TextSection::new(
+            "FPS 60",
+            TextAnchor::Screen(4.0, 4.0),
+            16.0,
+        ));
+        state.queue_text(
+            TextSection::new("Town", TextAnchor::World(Point3::new(0.0, 0.5, 0.0)), 14.0)
+                .with_color([1.0, 0.9, 0.2, 1.0]),
+        );
+    });
+}
+
+#[test]
+fn debug_ui_overlay() {
+    check_scene_with("debug_ui", |state| state.enable_debug_ui());
+}
+
+#[test]
+fn mouse_look() {
+    check_scene_with("mouse_look", |state| {
+        state.set_camera_mode(CameraMode::MouseLook);
+        // Turn right and look up a little, leaving the pentagon towards the bottom left.
+        state.mouse_motion((60.0, -40.0));
+        state.update();
+    });
+}
+
+#[test]
+#[allow(deprecated)]
+fn map_camera() {
+    check_scene_with("map_camera", |state| {
+        let device_id = unsafe { DeviceId::dummy() };
+        let move_to = |x, y| WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(x, y),
+            modifiers: ModifiersState::empty(),
+        };
+        let mouse_input = |state, button| WindowEvent::MouseInput {
+            device_id,
+            state,
+            button,
+            modifiers: ModifiersState::empty(),
+        };
+        let drag = |state: &mut State, button, from: (f64, f64), to: (f64, f64)| {
+            state.input(&move_to(from.0, from.1));
+            state.input(&mouse_input(ElementState::Pressed, button));
+            state.input(&move_to(to.0, to.1));
+            state.input(&mouse_input(ElementState::Released, button));
+            state.update();
+        };
+
+        state.set_camera_mode(CameraMode::Map);
+        // Orbit a little to the right and tilt towards a top-down view.
+        drag(state, MouseButton::Left, (80.0, 60.0), (40.0, 90.0));
+        // Slide the pentagon to the right.
+        drag(state, MouseButton::Right, (80.0, 60.0), (100.0, 60.0));
+        // Zoom in on the lower right.
+        state.input(&move_to(120.0, 90.0));
+        state.input(&WindowEvent::MouseWheel {
+            device_id,
+            delta: MouseScrollDelta::LineDelta(0.0, 3.0),
+            phase: TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        });
+        state.update();
+    });
+}
+
+#[test]
+fn orthographic_top_down() {
+    check_scene_with("orthographic_top_down", |state| {
+        let ground = state
+            .load_mesh("../tests/fixtures/ground.obj")
+            .expect("load ground plane");
+        state.show_mesh(ground);
+        state.set_camera_mode(CameraMode::Map);
+        state.map_camera_limits_mut().min_pitch = Deg(89.0).into();
+        state.update();
+        state.toggle_projection();
+        // Run a little longer than the switch is animated for.
+        for _ in 0..5 {
+            state.advance(ProjectionTransition::DURATION / 4);
+        }
+        // The view keeps the height it had at the target when it was perspective.
+        let expected = 2.0 * 5f32.sqrt() * Deg(22.5f32).tan();
+        match state.projection() {
+            Projection::Orthographic { height } => assert!((height - expected).abs() < 1e-4),
+            projection => panic!("expected an orthographic projection, got {:?}", projection),
+        }
+    });
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    time::Duration,
+};
+use winit::dpi::PhysicalSize;
+
+fn copy_resources(name: &str) -> PathBuf {
+    let source = PathBuf::from(common::RESOURCES);
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(&dir).expect("create assets copy");
+    for entry in fs::read_dir(source).expect("read resources") {
+        let entry = entry.expect("resource entry");
+        fs::copy(entry.path(), dir.join(entry.file_name())).expect("copy resource");
+    }
+    dir
+}
+
+/// Rewrites a file and moves its modification time forward, however coarse the file system's
+/// timestamps are.
+fn rewrite(path: &PathBuf, contents: &str) {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .expect("shader modification time");
+    fs::write(path, contents).expect("rewrite shader");
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(modified + Duration::from_secs(2)))
+        .expect("set shader modification time");
+}
+
+#[test]
+fn broken_shader_keeps_last_working_pipeline() {
+    let dir = copy_resources("hot-reload");
+    let mut state = match common::headless_state(PhysicalSize::new(32, 32), &dir) {
+        Some(state) => state,
+        None => return,
+    };
+    state.enable_shader_hot_reload();
+    assert!(!state.reload_changed_shaders());
+
+    let shader_path = dir.join("shader.wgsl");
+    let original = fs::read_to_string(&shader_path).expect("read shader");
+    let before = state.render_to_image().expect("render");
+
+    rewrite(
+        &shader_path,
+        &original.replace("fn fs_main", "fn fs_main oops"),
+    );
+    assert!(!state.reload_changed_shaders());
+    assert_eq!(before, state.render_to_image().expect("render"));
+
+    rewrite(
+        &shader_path,
+        &original.replace(
+            "return vec4<f32>(color, albedo.a);",
+            "return vec4<f32>(1.0, 0.0, 0.0, 1.0);",
+        ),
+    );
+    assert!(state.reload_changed_shaders());
+    let after = state.render_to_image().expect("render");
+    assert_eq!(after.get_pixel(16, 16).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn shader_changes_are_picked_up_a_quarter_second_later() {
+    let dir = copy_resources("hot-reload-interval");
+    let mut state = match common::headless_state(PhysicalSize::new(32, 32), &dir) {
+        Some(state) => state,
+        None => return,
+    };
+    state.enable_shader_hot_reload();
+    let before = state.render_to_image().expect("render");
+
+    let shader_path = dir.join("shader.wgsl");
+    let original = fs::read_to_string(&shader_path).expect("read shader");
+    rewrite(
+        &shader_path,
+        &original.replace(
+            "return vec4<f32>(color, albedo.a);",
+            "return vec4<f32>(1.0, 0.0, 0.0, 1.0);",
+        ),
+    );
+    state.advance(Duration::from_millis(200));
+    assert_eq!(before, state.render_to_image().expect("render"));
+
+    state.advance(Duration::from_millis(50));
+    let after = state.render_to_image().expect("render");
+    assert_eq!(after.get_pixel(16, 16).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn shaders_are_loaded_from_the_assets_directory() {
+    let dir = copy_resources("assets-shaders");
+    let shader_path = dir.join("shader.wgsl");
+    let original = fs::read_to_string(&shader_path).expect("read shader");
+    fs::write(
+        &shader_path,
+        original.replace(
+            "return vec4<f32>(color, albedo.a);",
+            "return vec4<f32>(0.0, 0.0, 1.0, 1.0);",
+        ),
+    )
+    .expect("rewrite shader");
+
+    let mut state = match common::headless_state(PhysicalSize::new(32, 32), &dir) {
+        Some(state) => state,
+        None => return,
+    };
+    let image = state.render_to_image().expect("render");
+    assert_eq!(image.get_pixel(16, 16).0, [0, 0, 255, 255]);
+}
@@ -0,0 +1,169 @@
+use std::{error::Error as _, fs, path::PathBuf};
+use town_links::{
+    err::Error,
+    input_map::{Action, Binding, InputMap},
+};
+use winit::event::{
+    DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+#[allow(deprecated)]
+fn key(keycode: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+    WindowEvent::KeyboardInput {
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(keycode),
+            modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: false,
+    }
+}
+
+#[allow(deprecated)]
+fn mouse(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+    WindowEvent::MouseInput {
+        device_id: unsafe { DeviceId::dummy() },
+        state,
+        button,
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[test]
+fn bundled_bindings_are_the_defaults() {
+    let input_map = InputMap::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/resources/bindings.ron"
+    ))
+    .expect("load bundled bindings");
+    assert_eq!(input_map.bindings(), InputMap::default().bindings());
+}
+
+#[test]
+fn chords_take_precedence_over_plain_keys() {
+    let mut input_map = InputMap::from_ron(
+        "{ NextChallenge: [Key(R)], ToggleProjection: [Chord([Ctrl], Key(R))] }",
+    )
+    .expect("parse bindings");
+
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::R, ElementState::Pressed)),
+        Some((Action::NextChallenge, ElementState::Pressed))
+    );
+    input_map.map_event(&key(VirtualKeyCode::R, ElementState::Released));
+
+    input_map.map_event(&WindowEvent::ModifiersChanged(ModifiersState::CTRL));
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::R, ElementState::Pressed)),
+        Some((Action::ToggleProjection, ElementState::Pressed))
+    );
+    // The release goes to the action the press triggered, even with Ctrl let go first.
+    input_map.map_event(&WindowEvent::ModifiersChanged(ModifiersState::empty()));
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::R, ElementState::Released)),
+        Some((Action::ToggleProjection, ElementState::Released))
+    );
+}
+
+#[test]
+fn plain_bindings_work_with_modifiers_held() {
+    let mut input_map = InputMap::default();
+    input_map.map_event(&WindowEvent::ModifiersChanged(ModifiersState::SHIFT));
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::W, ElementState::Pressed)),
+        Some((Action::MoveForward, ElementState::Pressed))
+    );
+}
+
+#[test]
+fn key_repeat_only_repeats_continuous_actions() {
+    let mut input_map = InputMap::default();
+    for keycode in [VirtualKeyCode::P, VirtualKeyCode::W] {
+        input_map.map_event(&key(keycode, ElementState::Pressed));
+    }
+
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::P, ElementState::Pressed)),
+        None
+    );
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::W, ElementState::Pressed)),
+        Some((Action::MoveForward, ElementState::Pressed))
+    );
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::P, ElementState::Released)),
+        Some((Action::ToggleProjection, ElementState::Released))
+    );
+    assert_eq!(
+        input_map.map_event(&key(VirtualKeyCode::P, ElementState::Pressed)),
+        Some((Action::ToggleProjection, ElementState::Pressed))
+    );
+}
+
+#[test]
+fn config_overrides_only_the_actions_it_lists() {
+    let mut input_map =
+        InputMap::from_ron("{ MapOrbit: [Mouse(Middle)], MapPan: [] }").expect("parse bindings");
+
+    assert_eq!(
+        input_map.bindings()[&Action::Quit],
+        vec![Binding::Key(VirtualKeyCode::Escape)]
+    );
+    assert_eq!(
+        input_map.map_event(&mouse(MouseButton::Middle, ElementState::Pressed)),
+        Some((Action::MapOrbit, ElementState::Pressed))
+    );
+    assert_eq!(
+        input_map.map_event(&mouse(MouseButton::Right, ElementState::Pressed)),
+        None
+    );
+}
+
+#[test]
+fn bindings_are_described_as_users_see_them() {
+    let input_map = InputMap::from_ron(
+        "{ TogglePause: [Key(Pause), Chord([Ctrl, Shift], Key(P))], MapPan: [Mouse(Right)], Quit: [] }",
+    )
+    .expect("parse bindings");
+
+    assert_eq!(
+        input_map.describe(Action::TogglePause).as_deref(),
+        Some("Pause/Ctrl+Shift+P")
+    );
+    assert_eq!(
+        input_map.describe(Action::MapPan).as_deref(),
+        Some("Right mouse")
+    );
+    assert_eq!(input_map.describe(Action::Quit), None);
+}
+
+#[test]
+fn invalid_bindings_are_rejected() {
+    assert!(InputMap::from_ron("{ Jump: [Key(Space)] }").is_err());
+    assert!(InputMap::from_ron("{ Quit: [Key(NotAKey)] }").is_err());
+}
+
+#[test]
+fn config_errors_name_the_file_and_keep_their_cause() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("bad-bindings.ron");
+    fs::write(&path, "{\n    Jump: [Key(Space)],\n}\n").expect("write bindings");
+    match InputMap::load(&path) {
+        Err(err @ Error::ConfigParseError(..)) => {
+            let message = err.report().to_string();
+            assert!(message.contains("bad-bindings.ron: 2:"), "{}", message);
+            assert!(err.source().is_some());
+        }
+        other => panic!("expected a parse error, got {:?}", other.err()),
+    }
+
+    let missing = path.with_file_name("no-such-bindings.ron");
+    match InputMap::load(&missing) {
+        Err(err @ Error::ConfigIoError(..)) => {
+            assert!(err.to_string().contains("no-such-bindings.ron"));
+            assert!(err.source().is_some());
+        }
+        other => panic!("expected an I/O error, got {:?}", other.err()),
+    }
+}
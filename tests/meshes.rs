@@ -0,0 +1,81 @@
+mod common;
+
+use town_links::{err::Error, state::State};
+use winit::dpi::PhysicalSize;
+
+/// Fixture directory relative to the assets root.
+const FIXTURES: &str = "../tests/fixtures/";
+
+fn headless_state() -> Option<State> {
+    common::headless_state(PhysicalSize::new(16, 16), common::RESOURCES)
+}
+
+#[test]
+fn obj_meshes_are_loaded_with_their_textures() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let handle = state
+        .load_mesh(FIXTURES.to_owned() + "quad.obj")
+        .expect("load quad.obj");
+    assert_eq!(
+        handle,
+        state
+            .load_mesh(FIXTURES.to_owned() + "quad.obj")
+            .expect("load cached quad.obj")
+    );
+
+    let asset = state.assets().mesh(handle);
+    assert_eq!(asset.meshes.len(), 1);
+    assert_eq!(asset.meshes[0].num_indices, 6);
+    assert_eq!(asset.materials.len(), 1);
+    assert_eq!(asset.materials[0].name, "house");
+}
+
+#[test]
+fn gltf_primitives_without_material_get_a_default_one() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let handle = state
+        .load_mesh(FIXTURES.to_owned() + "triangle.gltf")
+        .expect("load triangle.gltf");
+    let asset = state.assets().mesh(handle);
+    assert_eq!(asset.meshes.len(), 2);
+    assert!(asset.meshes.iter().all(|mesh| mesh.num_indices == 3));
+    assert_eq!(asset.materials.len(), 2);
+    assert_eq!(asset.meshes[0].material, 0);
+    assert_eq!(asset.meshes[1].material, 1);
+}
+
+#[test]
+fn unknown_mesh_formats_are_rejected() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    match state.load_mesh(FIXTURES.to_owned() + "quad.mtl") {
+        Err(Error::UnsupportedMeshFormat(path)) => assert!(path.ends_with("quad.mtl")),
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok(handle) => panic!("unexpected mesh {:?}", handle),
+    }
+}
+
+#[test]
+fn gltf_meshes_are_placed_once_per_scene_node() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let handle = state
+        .load_mesh(FIXTURES.to_owned() + "nodes.gltf")
+        .expect("load nodes.gltf");
+    let asset = state.assets().mesh(handle);
+    assert_eq!(asset.meshes.len(), 2);
+}
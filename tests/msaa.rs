@@ -0,0 +1,9 @@
+use town_links::msaa::SampleCount;
+
+#[test]
+fn unguaranteed_sample_counts_fall_back() {
+    assert_eq!(SampleCount::X1.guaranteed(), SampleCount::X1);
+    assert_eq!(SampleCount::X2.guaranteed(), SampleCount::X1);
+    assert_eq!(SampleCount::X4.guaranteed(), SampleCount::X4);
+    assert_eq!(SampleCount::X8.guaranteed(), SampleCount::X4);
+}
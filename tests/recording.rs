@@ -0,0 +1,168 @@
+mod common;
+
+use cgmath::Point3;
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+use town_links::{
+    camera::{Camera, Projection},
+    camera_controller::CameraMode,
+    challenge::{Challenge, ChallengeEnum},
+    err::Error,
+    input_map::{Action, Binding, InputMap},
+    recording::{RecordingHeader, Replay},
+    state::State,
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+fn headless_state() -> Option<State> {
+    common::headless_state(PhysicalSize::new(160, 120), common::RESOURCES)
+}
+
+#[allow(deprecated)]
+fn key(keycode: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+    WindowEvent::KeyboardInput {
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(keycode),
+            modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: false,
+    }
+}
+
+#[allow(deprecated)]
+fn session() -> Vec<Vec<WindowEvent<'static>>> {
+    let device_id = unsafe { DeviceId::dummy() };
+    let move_to = |x, y| WindowEvent::CursorMoved {
+        device_id,
+        position: PhysicalPosition::new(x, y),
+        modifiers: ModifiersState::empty(),
+    };
+    let mouse_input = |state| WindowEvent::MouseInput {
+        device_id,
+        state,
+        button: MouseButton::Left,
+        modifiers: ModifiersState::empty(),
+    };
+    vec![
+        vec![key(VirtualKeyCode::W, ElementState::Pressed)],
+        vec![],
+        vec![key(VirtualKeyCode::W, ElementState::Released)],
+        vec![
+            key(VirtualKeyCode::F2, ElementState::Pressed),
+            move_to(80.0, 60.0),
+        ],
+        vec![mouse_input(ElementState::Pressed), move_to(60.0, 70.0)],
+        vec![mouse_input(ElementState::Released)],
+        vec![WindowEvent::MouseWheel {
+            device_id,
+            delta: MouseScrollDelta::LineDelta(0.0, 2.0),
+            phase: TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        }],
+    ]
+}
+
+#[test]
+fn replaying_a_recording_renders_the_same_frame() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("session.ron");
+    let mut live = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+    live.start_recording(&path).expect("start recording");
+    let frames = session();
+    for events in &frames {
+        for event in events {
+            live.handle_window_event(event);
+        }
+        live.advance(FRAME);
+    }
+    live.stop_recording();
+    let expected = live.render_to_image().expect("headless render");
+    // The GL fallback can't have two devices at once.
+    drop(live);
+
+    let mut replay = Replay::load(&path).expect("load recording");
+    assert_eq!(replay.len(), frames.len());
+    let mut replayed = headless_state().expect("adapter available for the live session");
+    replay.play_to_end(&mut replayed);
+    assert!(replay.is_empty());
+    let actual = replayed.render_to_image().expect("headless render");
+
+    assert!(actual == expected, "replay differs from the live session");
+}
+
+#[test]
+fn replays_start_from_the_recorded_state() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("header.ron");
+    let mut live = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+    let bindings = HashMap::from([(Action::Quit, vec![Binding::Key(VirtualKeyCode::Q)])]);
+    live.set_input_map(InputMap::new(bindings));
+    live.set_camera_mode(CameraMode::Map);
+    live.set_camera(Camera {
+        eye: Point3::new(3.0, 4.0, 5.0),
+        target: Point3::new(1.0, 0.0, -1.0),
+        projection: Projection::Orthographic { height: 6.0 },
+        ..*live.camera()
+    });
+    live.set_challenge(Some(ChallengeEnum::Third).into());
+    live.clock_mut().set_scale(2.0);
+    live.clock_mut().set_paused(true);
+    live.start_recording(&path).expect("start recording");
+    live.advance(FRAME);
+    live.stop_recording();
+    let expected = RecordingHeader::from_state(&live);
+    drop(live);
+
+    let mut replayed = headless_state().expect("adapter available for the live session");
+    Replay::load(&path)
+        .expect("load recording")
+        .play_to_end(&mut replayed);
+    assert_eq!(RecordingHeader::from_state(&replayed), expected);
+    // Escape was unbound along with everything but Q.
+    replayed.handle_window_event(&key(VirtualKeyCode::Escape, ElementState::Pressed));
+    assert!(!replayed.quit_requested());
+    replayed.handle_window_event(&key(VirtualKeyCode::Q, ElementState::Pressed));
+    assert!(replayed.quit_requested());
+}
+
+#[test]
+fn malformed_recordings_report_the_line() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("malformed.ron");
+    let header = RecordingHeader {
+        size: PhysicalSize::new(160, 120),
+        bindings: HashMap::new(),
+        camera_mode: CameraMode::Orbit,
+        eye: Point3::new(0.0, 1.0, 2.0),
+        target: Point3::new(0.0, 0.0, 0.0),
+        projection: Projection::Perspective,
+        challenge: Challenge::default(),
+        time_scale: 1.0,
+        paused: false,
+    };
+    let source = format!(
+        "{}\n(events:[],delta:(secs:0,nanos:0))\n(events:[Jump])\n",
+        ron::to_string(&header).expect("serialize header")
+    );
+    fs::write(&path, source).expect("write recording");
+    match Replay::load(&path) {
+        Err(Error::RecordingParseError(_, line, _)) => assert_eq!(line, 3),
+        other => panic!(
+            "expected a parse error, got {:?}",
+            other.map(|replay| replay.len())
+        ),
+    }
+}
@@ -0,0 +1,20 @@
+use town_links::render_target::bgra_to_rgba;
+use wgpu::TextureFormat;
+
+#[test]
+fn bgra_texels_are_reordered_to_rgba() {
+    for format in [TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb] {
+        let mut pixels = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        bgra_to_rgba(format, &mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 4, 7, 6, 5, 8], "{:?}", format);
+    }
+}
+
+#[test]
+fn rgba_texels_are_left_alone() {
+    for format in [TextureFormat::Rgba8Unorm, TextureFormat::Rgba8UnormSrgb] {
+        let mut pixels = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        bgra_to_rgba(format, &mut pixels);
+        assert_eq!(pixels, [1, 2, 3, 4, 5, 6, 7, 8], "{:?}", format);
+    }
+}
@@ -0,0 +1,60 @@
+use cgmath::{Deg, Matrix4, Quaternion, Rotation3, Vector3, Vector4};
+use town_links::{
+    model::Model,
+    scene::{Node, Scene},
+};
+
+fn translated(x: f32, y: f32, z: f32) -> Model {
+    Model {
+        position: Vector3::new(x, y, z),
+        ..Model::default()
+    }
+}
+
+fn origin_of(matrix: Matrix4<f32>) -> Vector4<f32> {
+    matrix * Vector4::new(0.0, 0.0, 0.0, 1.0)
+}
+
+#[test]
+fn children_move_with_their_parents() {
+    let mut scene = Scene::new();
+    let town = scene.add(None, Node::new("town", translated(10.0, 0.0, 0.0)));
+    let house = scene.add(Some(town), Node::new("house", translated(0.0, 0.0, 2.0)));
+    let door = scene.add(Some(house), Node::new("door", translated(0.0, 1.0, 0.0)));
+
+    scene.update_world_matrices();
+    assert_eq!(
+        origin_of(scene.node(door).world_matrix()),
+        Vector4::new(10.0, 1.0, 2.0, 1.0)
+    );
+
+    scene.node_mut(town).transform.rotation = Quaternion::from_angle_y(Deg(90.0));
+    scene.update_world_matrices();
+    let door_origin = origin_of(scene.node(door).world_matrix());
+    let expected = Vector4::new(12.0, 1.0, 0.0, 1.0);
+    assert!(
+        (0..4).all(|i| (door_origin[i] - expected[i]).abs() < 1e-5),
+        "door at {:?}, expected {:?}",
+        door_origin,
+        expected
+    );
+    assert_eq!(scene.node(door).parent(), Some(house));
+    assert_eq!(scene.node(town).children(), &[house]);
+}
+
+#[test]
+fn hidden_nodes_hide_their_children() {
+    let mut scene = Scene::new();
+    let first = scene.add(None, Node::new("first", Model::default()));
+    let child = scene.add(Some(first), Node::new("child", Model::default()));
+    let second = scene.add(None, Node::new("second", Model::default()));
+    let grandchild = scene.add(Some(child), Node::new("grandchild", Model::default()));
+
+    assert_eq!(
+        scene.visible_nodes(),
+        vec![first, child, grandchild, second]
+    );
+
+    scene.node_mut(child).visible = false;
+    assert_eq!(scene.visible_nodes(), vec![first, second]);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, UNIX_EPOCH},
+};
+use town_links::{
+    err::Error,
+    state::{timestamp, State},
+};
+use winit::dpi::PhysicalSize;
+
+fn headless_state() -> Option<State> {
+    common::headless_state(PhysicalSize::new(160, 120), common::RESOURCES)
+}
+
+#[test]
+fn requested_screenshots_are_saved_after_rendering() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("screenshots");
+    let _ = fs::remove_dir_all(&dir);
+    state.set_screenshot_dir(&dir);
+
+    state.render().expect("render");
+    assert!(!dir.exists(), "nothing saved without a request");
+
+    state.request_screenshot();
+    state.render().expect("render and save");
+    let saved = fs::read_dir(&dir)
+        .expect("screenshot directory")
+        .map(|entry| entry.expect("directory entry").path())
+        .collect::<Vec<_>>();
+    assert_eq!(saved.len(), 1);
+
+    let name = saved[0].file_name().unwrap().to_string_lossy().into_owned();
+    // screenshot-YYYY-MM-DD_hh-mm-ss.mmm.png
+    assert!(name.starts_with("screenshot-") && name.ends_with(".png"));
+    assert_eq!(name.len(), "screenshot-2024-05-01_13-45-07.250.png".len());
+
+    let image = image::open(&saved[0]).expect("open screenshot").to_rgba8();
+    assert_eq!(image.dimensions(), (160, 120));
+    assert!(image == state.render_to_image().expect("headless render"));
+}
+
+#[test]
+fn failed_saves_name_the_file() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("missing/frame.png");
+    let _ = fs::remove_dir_all(path.parent().expect("parent directory"));
+    match state.save_png(&path) {
+        Err(err @ Error::ImageSaveError(..)) => {
+            assert!(err.to_string().contains(&path.display().to_string()))
+        }
+        other => panic!("expected a save error, got {:?}", other),
+    }
+}
+
+#[test]
+fn timestamps_are_utc_calendar_dates() {
+    let at = |secs: u64, millis: u64| {
+        timestamp(UNIX_EPOCH + Duration::from_millis(secs * 1_000 + millis))
+    };
+    assert_eq!(at(0, 0), "1970-01-01_00-00-00.000");
+    assert_eq!(at(1_709_210_096, 789), "2024-02-29_12-34-56.789");
+    // 2100 is divisible by 4 but isn't a leap year.
+    assert_eq!(at(4_107_542_399, 999), "2100-02-28_23-59-59.999");
+    assert_eq!(at(4_107_542_400, 0), "2100-03-01_00-00-00.000");
+}
+
+#[test]
+fn screenshots_never_replace_each_other() {
+    let mut state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("screenshots-repeated");
+    let _ = fs::remove_dir_all(&dir);
+    state.set_screenshot_dir(&dir);
+
+    let paths = (0..3)
+        .map(|_| state.save_screenshot().expect("save screenshot"))
+        .collect::<Vec<_>>();
+    assert!(paths[0] != paths[1] && paths[1] != paths[2] && paths[0] != paths[2]);
+    assert_eq!(fs::read_dir(&dir).expect("screenshot directory").count(), 3);
+}
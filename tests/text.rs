@@ -0,0 +1,65 @@
+mod common;
+
+use town_links::{
+    state::State,
+    text::{TextAnchor, TextSection},
+};
+use winit::dpi::PhysicalSize;
+
+fn headless_state() -> Option<State> {
+    common::headless_state(PhysicalSize::new(16, 16), common::RESOURCES)
+}
+
+#[test]
+fn kerned_pairs_are_narrower_than_their_glyphs() {
+    let state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let (a, _) = state.measure_text("A", 32.0);
+    let (v, _) = state.measure_text("V", 32.0);
+    let (av, _) = state.measure_text("AV", 32.0);
+    assert!(av < a + v, "AV is {} wide, A and V {} and {}", av, a, v);
+}
+
+#[test]
+fn lines_stack_and_keep_the_widest_width() {
+    let state = match headless_state() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let (short, one_line) = state.measure_text("Town", 20.0);
+    let (long, _) = state.measure_text("Town hall", 20.0);
+    let (width, two_lines) = state.measure_text("Town\nTown hall", 20.0);
+    assert_eq!(width, long);
+    assert!(short < long);
+    assert!(two_lines >= 2.0 * one_line);
+}
+
+#[test]
+fn glyphs_are_drawn_after_the_atlas_fills_up() {
+    let mut state = match common::headless_state(PhysicalSize::new(64, 64), common::RESOURCES) {
+        Some(state) => state,
+        None => return,
+    };
+    let blank = state.render_to_image().expect("render");
+
+    // More glyphs than the atlas has room for at this size, all off screen.
+    state.queue_text(TextSection::new(
+        "ABCDEFGHIJKLMNOPQRSTUVXYZ",
+        TextAnchor::Screen(64.0, 64.0),
+        400.0,
+    ));
+    state.render_to_image().expect("render");
+
+    // A glyph first drawn once the atlas is full still shows up, covering the window.
+    state.queue_text(TextSection::new(
+        "W",
+        TextAnchor::Screen(-100.0, -150.0),
+        400.0,
+    ));
+    let drawn = state.render_to_image().expect("render");
+    assert_ne!(drawn, blank);
+}